@@ -0,0 +1,232 @@
+use crate::error::Error;
+
+/// Returns whether a location is one of Webby's built-in pages. Only these pages can use the
+/// privileged script functions.
+pub fn is_about(location: &str) -> bool {
+    location.starts_with("about:")
+}
+
+/// Returns the UI definition of one of Webby's built-in pages, for a location like
+/// `about:history`. The definitions are embedded in the binary.
+pub fn source(location: &str) -> crate::Result<&'static str> {
+    // A fragment refers to part of the page, not a different one.
+    let page = location.split('#').next().unwrap_or(location);
+    match page {
+        "about:blank" => Ok(include_str!("about/blank.ui")),
+        "about:bookmarks" => Ok(include_str!("about/bookmarks.ui")),
+        "about:history" => Ok(include_str!("about/history.ui")),
+        "about:settings" => Ok(include_str!("about/settings.ui")),
+        "about:version" => Ok(include_str!("about/version.ui")),
+        _ => Err(Error::UnknownPageError(location.to_string())),
+    }
+}
+
+/// Why a page couldn't be shown, as explained on its error page.
+#[derive(Debug, PartialEq)]
+enum Failure {
+    ServerNotFound,
+    ConnectionFailed,
+    TimedOut,
+    InsecureConnection,
+    Status(reqwest::StatusCode),
+    UnsupportedContent,
+    /// The UI definition is invalid, with the line and column of the error if it's known.
+    InvalidPage(Option<(usize, usize)>),
+    UnreadableFile,
+    UnknownPage,
+    Other,
+}
+
+impl Failure {
+    fn of(err: &Error) -> Self {
+        match err {
+            Error::HttpError(err) => Failure::of_http(err),
+            Error::HttpStatusError(status) => Failure::Status(*status),
+            Error::NoContentTypeError
+            | Error::HeaderToStrError(_)
+            | Error::MimeParseError(_)
+            | Error::UnsupportedCharsetError(_)
+            | Error::UnsupportedContentTypeError(_)
+            | Error::UnknownFileTypeError(_) => Failure::UnsupportedContent,
+            Error::ParseError { line, column, .. } => Failure::InvalidPage(Some((*line, *column))),
+            Error::XmlError(_)
+            | Error::XmlAttrError(_)
+            | Error::GlibError(_)
+            | Error::FromUtf8Error(_) => Failure::InvalidPage(None),
+            Error::IoError(_) => Failure::UnreadableFile,
+            Error::UnknownPageError(_) => Failure::UnknownPage,
+            _ => Failure::Other,
+        }
+    }
+
+    /// reqwest doesn't say why a request failed beyond whether it was while connecting, so the
+    /// underlying errors are checked for DNS and TLS failures.
+    fn of_http(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Failure::TimedOut;
+        }
+        let mut causes = String::new();
+        let mut source = std::error::Error::source(err);
+        while let Some(cause) = source {
+            causes.push_str(&cause.to_string().to_lowercase());
+            causes.push('\n');
+            source = cause.source();
+        }
+        if causes.contains("dns error") || causes.contains("failed to lookup address") {
+            Failure::ServerNotFound
+        } else if ["certificate", "tls", "ssl", "handshake"]
+            .iter()
+            .any(|word| causes.contains(word))
+        {
+            Failure::InsecureConnection
+        } else if err.is_connect() {
+            Failure::ConnectionFailed
+        } else {
+            Failure::Other
+        }
+    }
+
+    /// Returns the icon, title and message shown for the failure.
+    fn describe(&self, location: &str) -> (&'static str, String, String) {
+        let host = url::Url::parse(location)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_else(|| location.to_string());
+        match self {
+            Failure::ServerNotFound => (
+                "network-error-symbolic",
+                String::from("Server not found"),
+                format!("Webby couldn't find the server at {}. Check the address for typos, and that you're connected to the network.", host),
+            ),
+            Failure::ConnectionFailed => (
+                "network-error-symbolic",
+                String::from("Unable to connect"),
+                format!("The server at {} refused the connection or couldn't be reached. It may be down, or not listening on this port.", host),
+            ),
+            Failure::TimedOut => (
+                "network-error-symbolic",
+                String::from("Connection timed out"),
+                format!("The server at {} took too long to respond.", host),
+            ),
+            Failure::InsecureConnection => (
+                "channel-insecure-symbolic",
+                String::from("Secure connection failed"),
+                format!("A secure connection to {} couldn't be established. If the server uses a self-signed certificate, it can be trusted by starting Webby with --add-root-cert.", host),
+            ),
+            Failure::Status(status) => (
+                "dialog-error-symbolic",
+                format!(
+                    "{} {}",
+                    status.as_u16(),
+                    status.canonical_reason().unwrap_or("Error")
+                ),
+                if *status == reqwest::StatusCode::NOT_FOUND {
+                    format!("The server at {} has no page at this address.", host)
+                } else if status.is_client_error() {
+                    format!("The server at {} couldn't handle the request.", host)
+                } else {
+                    format!("The server at {} ran into a problem while responding.", host)
+                },
+            ),
+            Failure::UnsupportedContent => (
+                "dialog-question-symbolic",
+                String::from("Unsupported content"),
+                String::from("This page isn't a UI definition or plain text, so Webby can't show it."),
+            ),
+            Failure::InvalidPage(position) => (
+                "dialog-warning-symbolic",
+                String::from("This page couldn't be shown"),
+                match position {
+                    Some((line, column)) => format!(
+                        "The page's UI definition is invalid at line {}, column {}.",
+                        line, column
+                    ),
+                    None => String::from("The page's UI definition is invalid."),
+                },
+            ),
+            Failure::UnreadableFile => (
+                "dialog-error-symbolic",
+                String::from("File couldn't be read"),
+                String::from("The file may not exist, or Webby may not be allowed to read it."),
+            ),
+            Failure::UnknownPage => (
+                "dialog-question-symbolic",
+                String::from("Page not found"),
+                String::from("Webby has no built-in page with this name."),
+            ),
+            Failure::Other => (
+                "dialog-error-symbolic",
+                String::from("Something went wrong"),
+                String::from("The page couldn't be loaded."),
+            ),
+        }
+    }
+}
+
+/// Returns the UI definition of a page explaining why `location` couldn't be shown, with a button
+/// to try again.
+pub fn error_page(err: &Error, location: &str) -> String {
+    let (icon, title, message) = Failure::of(err).describe(location);
+    let escape = |s: &str| quick_xml::escape::escape(s).into_owned();
+    include_str!("about/error.ui")
+        .replace("{{icon}}", icon)
+        .replace("{{title}}", &escape(&title))
+        .replace("{{message}}", &escape(&message))
+        .replace("{{location}}", &escape(location))
+        .replace("{{details}}", &escape(&err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_pages_parse() -> crate::Result<()> {
+        for location in [
+            "about:blank",
+            "about:bookmarks",
+            "about:history",
+            "about:settings",
+            "about:version",
+        ] {
+            assert!(is_about(location));
+            let definition = crate::ui::Definition::new(source(location)?.to_string())?;
+            assert!(definition.buildable.contains(r#"id="body""#));
+        }
+        assert!(source("about:history#search").is_ok());
+        assert!(source("about:missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_error_page() -> crate::Result<()> {
+        let not_found = Error::HttpStatusError(reqwest::StatusCode::NOT_FOUND);
+        let definition =
+            crate::ui::Definition::new(error_page(&not_found, "http://localhost:8000/a?b&c"))?;
+        assert_eq!(definition.title.as_deref(), Some("404 Not Found"));
+        assert!(definition.buildable.contains("localhost:8000/a?b&amp;c"));
+        assert!(definition.buildable.contains(r#"id="retry""#));
+
+        let server_error = Error::HttpStatusError(reqwest::StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            Failure::of(&server_error),
+            Failure::Status(reqwest::StatusCode::BAD_GATEWAY)
+        );
+        assert_eq!(
+            Failure::of(&Error::UnsupportedContentTypeError(String::from(
+                "image/png"
+            ))),
+            Failure::UnsupportedContent
+        );
+
+        // Parse errors can quote the page's own markup, which must not break the error page.
+        let invalid = crate::ui::Definition::new(String::from("<interface><object></interface>"))
+            .err()
+            .unwrap();
+        assert_eq!(Failure::of(&invalid), Failure::InvalidPage(Some((1, 20))));
+        let details = Error::Any(String::from("expected </object>, found </interface>"));
+        let definition = crate::ui::Definition::new(error_page(&details, "file:///tmp/index.ui"))?;
+        assert!(definition.buildable.contains("expected &lt;/object&gt;"));
+        Ok(())
+    }
+}
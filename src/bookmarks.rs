@@ -0,0 +1,269 @@
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub type SharedBookmarks = Arc<Mutex<Bookmarks>>;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+}
+
+/// The user's bookmarks, with at most one per URL.
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    /// File that the bookmarks are saved to. If there is none, they aren't saved.
+    path: Option<PathBuf>,
+    list: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Loads bookmarks from a JSON file. It doesn't need to exist yet; it will be created the
+    /// first time they're saved.
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let list = match std::fs::read_to_string(&path) {
+            Ok(s) => from_json(&s)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Bookmarks {
+            path: Some(path),
+            list,
+        })
+    }
+
+    pub fn save(&self) -> crate::Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, to_json(&self.list)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> &[Bookmark] {
+        &self.list
+    }
+
+    pub fn get(&self, url: &str) -> Option<&Bookmark> {
+        self.list.iter().find(|bookmark| bookmark.url == url)
+    }
+
+    /// Adds a bookmark, returning false if the URL was already bookmarked.
+    pub fn add(&mut self, title: String, url: String) -> bool {
+        if self.get(&url).is_some() {
+            return false;
+        }
+        self.list.push(Bookmark { title, url });
+        true
+    }
+
+    /// Renames the bookmark for a URL, returning false if there isn't one.
+    pub fn rename(&mut self, url: &str, title: String) -> bool {
+        match self.list.iter_mut().find(|bookmark| bookmark.url == url) {
+            Some(bookmark) => {
+                bookmark.title = title;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the bookmark for a URL, returning false if there isn't one.
+    pub fn remove(&mut self, url: &str) -> bool {
+        let len = self.list.len();
+        self.list.retain(|bookmark| bookmark.url != url);
+        self.list.len() != len
+    }
+
+    /// Adds the bookmarks from a JSON or XBEL file, depending on its extension, skipping any URLs
+    /// that are already bookmarked. Returns the number of bookmarks added.
+    pub fn import(&mut self, path: &Path) -> crate::Result<usize> {
+        let s = std::fs::read_to_string(path)?;
+        let imported = if is_xbel(path) {
+            from_xbel(&s)?
+        } else {
+            from_json(&s)?
+        };
+        Ok(imported
+            .into_iter()
+            .filter(|bookmark| self.add(bookmark.title.clone(), bookmark.url.clone()))
+            .count())
+    }
+
+    /// Writes the bookmarks to a JSON or XBEL file, depending on its extension.
+    pub fn export(&self, path: &Path) -> crate::Result<()> {
+        let s = if is_xbel(path) {
+            to_xbel(&self.list)?
+        } else {
+            to_json(&self.list)?
+        };
+        std::fs::write(path, s)?;
+        Ok(())
+    }
+}
+
+fn is_xbel(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("xbel"))
+}
+
+fn to_json(list: &[Bookmark]) -> crate::Result<String> {
+    Ok(serde_json::to_string_pretty(list)?)
+}
+
+fn from_json(s: &str) -> crate::Result<Vec<Bookmark>> {
+    Ok(serde_json::from_str(s)?)
+}
+
+/// Writes bookmarks in the XML Bookmark Exchange Language, which most browsers can import.
+fn to_xbel(list: &[Bookmark]) -> crate::Result<String> {
+    let mut writer = quick_xml::Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    let mut xbel = BytesStart::new("xbel");
+    xbel.push_attribute(("version", "1.0"));
+    writer.write_event(Event::Start(xbel))?;
+    for bookmark in list {
+        let mut start = BytesStart::new("bookmark");
+        start.push_attribute(("href", bookmark.url.as_str()));
+        writer.write_event(Event::Start(start))?;
+        writer.write_event(Event::Start(BytesStart::new("title")))?;
+        writer.write_event(Event::Text(BytesText::new(&bookmark.title)))?;
+        writer.write_event(Event::End(BytesEnd::new("title")))?;
+        writer.write_event(Event::End(BytesEnd::new("bookmark")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("xbel")))?;
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+/// Reads bookmarks from an XBEL document. Folders aren't supported, so the bookmarks inside of
+/// them are flattened into a single list.
+fn from_xbel(s: &str) -> crate::Result<Vec<Bookmark>> {
+    let mut reader = quick_xml::Reader::from_str(s);
+    let mut list = Vec::new();
+    let mut buf = Vec::new();
+
+    let mut current: Option<Bookmark> = None;
+    let mut reading_title = false;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Eof => break,
+            Event::Start(ref bs) => match bs.name().as_ref() {
+                b"bookmark" => current = Some(xbel_bookmark(bs)?),
+                b"title" => reading_title = current.is_some(),
+                _ => (),
+            },
+            Event::Empty(ref bs) if bs.name().as_ref() == b"bookmark" => {
+                let mut bookmark = xbel_bookmark(bs)?;
+                bookmark.title = bookmark.url.clone();
+                list.push(bookmark);
+            }
+            Event::Text(bt) => {
+                if let (true, Some(bookmark)) = (reading_title, current.as_mut()) {
+                    bookmark.title.push_str(&bt.unescape()?);
+                }
+            }
+            Event::End(be) => match be.name().as_ref() {
+                b"bookmark" => {
+                    if let Some(mut bookmark) = current.take() {
+                        if !bookmark.title.trim().is_empty() {
+                            bookmark.title = bookmark.title.trim().to_string();
+                        } else {
+                            // Untitled bookmarks are shown by URL.
+                            bookmark.title = bookmark.url.clone();
+                        }
+                        list.push(bookmark);
+                    }
+                }
+                b"title" => reading_title = false,
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(list)
+}
+
+/// Reads the start of an XBEL bookmark element. The title comes from a child element, if any.
+fn xbel_bookmark(bs: &BytesStart) -> crate::Result<Bookmark> {
+    let mut url = String::new();
+    for attr in bs.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == b"href" {
+            url = attr.unescape_value()?.into_owned();
+        }
+    }
+    Ok(Bookmark {
+        title: String::new(),
+        url,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bookmark(title: &str, url: &str) -> Bookmark {
+        Bookmark {
+            title: String::from(title),
+            url: String::from(url),
+        }
+    }
+
+    #[test]
+    pub fn test_add_rename_remove() {
+        let mut bookmarks = Bookmarks::default();
+        assert!(bookmarks.add(String::from("Home"), String::from("http://localhost:8000")));
+        assert!(!bookmarks.add(String::from("Again"), String::from("http://localhost:8000")));
+        assert!(bookmarks.rename("http://localhost:8000", String::from("Local")));
+        assert_eq!(
+            bookmarks.list(),
+            &[bookmark("Local", "http://localhost:8000")]
+        );
+        assert!(bookmarks.remove("http://localhost:8000"));
+        assert!(!bookmarks.remove("http://localhost:8000"));
+        assert!(bookmarks.list().is_empty());
+    }
+
+    #[test]
+    pub fn test_json() -> crate::Result<()> {
+        let list = vec![bookmark("Home", "http://localhost:8000")];
+        assert_eq!(from_json(&to_json(&list)?)?, list);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_xbel() -> crate::Result<()> {
+        let list = vec![
+            bookmark("Home", "http://localhost:8000"),
+            bookmark("Q&A <1>", "http://localhost:8000/?a=1&b=2"),
+        ];
+        assert_eq!(from_xbel(&to_xbel(&list)?)?, list);
+
+        let s = r#"<?xml version="1.0"?>
+            <xbel version="1.0">
+                <folder>
+                    <title>Folder</title>
+                    <bookmark href="http://example.com/"><title>Example</title></bookmark>
+                </folder>
+                <bookmark href="http://localhost:8000/"/>
+            </xbel>"#;
+        assert_eq!(
+            from_xbel(s)?,
+            vec![
+                bookmark("Example", "http://example.com/"),
+                bookmark("http://localhost:8000/", "http://localhost:8000/"),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use glib::clone;
+use gtk::glib;
+use gtk::prelude::*;
+use std::sync::Arc;
+
+use crate::cookies::{Cookie, Jar};
+
+/// Dialog listing the cookies in the shared jar by site, so that they can be deleted.
+pub struct CookieManager {
+    window: gtk::Window,
+}
+
+impl CookieManager {
+    pub fn new(parent: &impl IsA<gtk::Window>, jar: Arc<Jar>) -> Self {
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::None);
+        fill_list(&list, &jar);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hexpand(true)
+            .vexpand(true)
+            .child(&list)
+            .build();
+
+        let clear_all = gtk::Button::with_label("Clear All");
+        clear_all.set_halign(gtk::Align::End);
+        clear_all.connect_clicked(clone!(@weak list, @strong jar => move |_| {
+            if let Err(err) = jar.clear() {
+                println!("Failed to clear cookies: {}", err);
+            }
+            fill_list(&list, &jar);
+        }));
+
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        container.set_margin_top(6);
+        container.set_margin_bottom(6);
+        container.set_margin_start(6);
+        container.set_margin_end(6);
+        container.append(&scrolled_window);
+        container.append(&clear_all);
+
+        let window = gtk::Window::builder()
+            .title("Cookies")
+            .width_request(500)
+            .height_request(400)
+            .transient_for(parent)
+            .child(&container)
+            .build();
+
+        CookieManager { window }
+    }
+
+    pub fn show(&self) {
+        self.window.present();
+    }
+}
+
+/// Replaces the contents of `list` with a row for each site that has cookies.
+fn fill_list(list: &gtk::ListBox, jar: &Arc<Jar>) {
+    while let Some(row) = list.first_child() {
+        list.remove(&row);
+    }
+
+    // Cookies are sorted by domain, so each site's cookies are next to each other.
+    let mut sites: Vec<(String, Vec<Cookie>)> = Vec::new();
+    for cookie in jar.list() {
+        match sites.last_mut() {
+            Some((domain, cookies)) if *domain == cookie.domain => cookies.push(cookie),
+            _ => sites.push((cookie.domain.clone(), vec![cookie])),
+        }
+    }
+
+    if sites.is_empty() {
+        list.append(&gtk::Label::new(Some("No cookies")));
+        return;
+    }
+
+    for (domain, cookies) in sites {
+        let details = cookies
+            .iter()
+            .map(|cookie| {
+                format!(
+                    "{} = {} (path {}, {})",
+                    cookie.name,
+                    cookie.value,
+                    cookie.path,
+                    match &cookie.expires {
+                        Some(date) => format!("expires {}", date),
+                        None => String::from("session"),
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let details_label = gtk::Label::new(Some(&details));
+        details_label.set_xalign(0.0);
+        details_label.set_wrap(true);
+        details_label.set_selectable(true);
+
+        let title = match cookies.len() {
+            1 => format!("{} (1 cookie)", domain),
+            n => format!("{} ({} cookies)", domain, n),
+        };
+        let expander = gtk::Expander::new(Some(&title));
+        expander.set_child(Some(&details_label));
+        expander.set_hexpand(true);
+
+        let delete = gtk::Button::with_label("Delete");
+        delete.set_valign(gtk::Align::Start);
+        delete.connect_clicked(clone!(@weak list, @strong jar => move |_| {
+            if let Err(err) = jar.remove_site(&domain) {
+                println!("Failed to delete cookies for {}: {}", &domain, err);
+            }
+            fill_list(&list, &jar);
+        }));
+
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        row.append(&expander);
+        row.append(&delete);
+        list.append(&row);
+    }
+}
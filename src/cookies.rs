@@ -0,0 +1,225 @@
+use reqwest::header::HeaderValue;
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// A cookie jar shared by every window, and saved to disk so that logins survive restarts. As in
+/// a web browser, session cookies are only kept until Webby exits.
+#[derive(Default)]
+pub struct Jar {
+    /// File that the cookies are saved to. If there is none, they aren't saved.
+    path: Option<PathBuf>,
+    store: RwLock<cookie_store::CookieStore>,
+    /// Whether any cookies have been set since the jar was loaded or last saved.
+    changed: AtomicBool,
+}
+
+/// A cookie, as listed in the cookie manager.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+    pub domain: String,
+    pub path: String,
+    pub name: String,
+    pub value: String,
+    /// Expiration date, or None for session cookies.
+    pub expires: Option<String>,
+}
+
+impl Jar {
+    /// Loads cookies from a JSON file. It doesn't need to exist yet; it will be created the first
+    /// time the cookies are saved.
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let store = match std::fs::File::open(&path) {
+            Ok(file) => cookie_store::CookieStore::load_json(BufReader::new(file))
+                .map_err(crate::error::Error::CookieStoreError)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                cookie_store::CookieStore::default()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Jar {
+            path: Some(path),
+            store: RwLock::new(store),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    /// Saves the cookies. They're written to a temporary file first, which then replaces the old
+    /// one, so that the file is never left half written. Only the user can read it, since it
+    /// holds their logins.
+    pub fn save(&self) -> crate::Result<()> {
+        if let Some(path) = &self.path {
+            let mut buf = Vec::new();
+            self.store
+                .read()
+                .unwrap()
+                .save_json(&mut buf)
+                .map_err(crate::error::Error::CookieStoreError)?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+
+            let temp_path = path.with_extension("json.tmp");
+            match std::fs::remove_file(&temp_path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => (),
+            }
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(&temp_path)?;
+            file.write_all(&buf)?;
+            file.sync_all()?;
+            std::fs::rename(&temp_path, path)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the cookies if any have been set since they were loaded or last saved. Cookies are
+    /// set by requests on other threads, so this is called periodically from the main thread
+    /// rather than by each request.
+    pub fn save_changes(&self) -> crate::Result<()> {
+        if self.changed.swap(false, Ordering::SeqCst) {
+            if let Err(err) = self.save() {
+                self.changed.store(true, Ordering::SeqCst);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns every unexpired cookie, sorted by domain.
+    pub fn list(&self) -> Vec<Cookie> {
+        let mut cookies: Vec<Cookie> = self
+            .store
+            .read()
+            .unwrap()
+            .iter_unexpired()
+            .map(|cookie| Cookie {
+                domain: cookie
+                    .domain
+                    .as_cow()
+                    .map(|domain| domain.into_owned())
+                    .unwrap_or_default(),
+                path: String::from(&cookie.path),
+                name: cookie.name().to_string(),
+                value: cookie.value().to_string(),
+                expires: match &cookie.expires {
+                    cookie_store::CookieExpiration::AtUtc(time) => Some(time.date().to_string()),
+                    cookie_store::CookieExpiration::SessionEnd => None,
+                },
+            })
+            .collect();
+        cookies.sort_by(|a, b| (&a.domain, &a.path, &a.name).cmp(&(&b.domain, &b.path, &b.name)));
+        cookies
+    }
+
+    /// Deletes every cookie set for a domain.
+    pub fn remove_site(&self, domain: &str) -> crate::Result<()> {
+        {
+            let mut store = self.store.write().unwrap();
+            let keys: Vec<(String, String)> = store
+                .iter_any()
+                .filter(|cookie| cookie.domain.as_cow().as_deref() == Some(domain))
+                .map(|cookie| (String::from(&cookie.path), cookie.name().to_string()))
+                .collect();
+            for (path, name) in keys {
+                store.remove(domain, &path, &name);
+            }
+        }
+        self.save()
+    }
+
+    /// Deletes every cookie.
+    pub fn clear(&self) -> crate::Result<()> {
+        self.store.write().unwrap().clear();
+        self.save()
+    }
+}
+
+impl reqwest::cookie::CookieStore for Jar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &url::Url) {
+        let mut changed = false;
+        {
+            let mut store = self.store.write().unwrap();
+            for header in cookie_headers {
+                if let Ok(s) = std::str::from_utf8(header.as_bytes()) {
+                    changed |= store.parse(s, url).is_ok();
+                }
+            }
+        }
+
+        if changed {
+            self.changed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn cookies(&self, url: &url::Url) -> Option<HeaderValue> {
+        let s = self
+            .store
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        if s.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&s).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::cookie::CookieStore;
+
+    fn set_cookies(jar: &Jar, url: &str, headers: &[&str]) {
+        let headers: Vec<HeaderValue> = headers
+            .iter()
+            .map(|header| HeaderValue::from_str(header).unwrap())
+            .collect();
+        jar.set_cookies(&mut headers.iter(), &url::Url::parse(url).unwrap());
+    }
+
+    #[test]
+    pub fn test_jar() -> crate::Result<()> {
+        let path = std::env::temp_dir().join(format!("webby-cookies-{}.json", std::process::id()));
+        let jar = Jar::load(path.clone())?;
+        set_cookies(
+            &jar,
+            "http://localhost:8000/login",
+            &["session=abc; Path=/; Max-Age=3600", "theme=dark; Path=/"],
+        );
+        set_cookies(&jar, "http://example.com/", &["id=1; Max-Age=3600"]);
+        let header = CookieStore::cookies(&jar, &url::Url::parse("http://localhost:8000/").unwrap());
+        let mut values: Vec<&str> = header.as_ref().unwrap().to_str().unwrap().split("; ").collect();
+        values.sort();
+        assert_eq!(values, vec!["session=abc", "theme=dark"]);
+
+        // Only persistent cookies are saved.
+        jar.save_changes()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+        let loaded = Jar::load(path.clone())?;
+        let names: Vec<String> = loaded.list().into_iter().map(|cookie| cookie.name).collect();
+        assert_eq!(names, vec!["id", "session"]);
+
+        loaded.remove_site("localhost")?;
+        let names: Vec<String> = loaded.list().into_iter().map(|cookie| cookie.name).collect();
+        assert_eq!(names, vec!["id"]);
+
+        loaded.clear()?;
+        assert!(Jar::load(path.clone())?.list().is_empty());
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use glib::clone;
-use gtk::glib;
 use gtk::prelude::*;
+use gtk::{gdk, glib};
 use sourceview5::prelude::*;
-use sourceview5::{Buffer, LanguageManager, StyleSchemeManager, View};
+use sourceview5::{Buffer, LanguageManager, MarkAttributes, StyleSchemeManager, View};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 const PLACEHOLDER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
 <interface>
@@ -14,6 +16,9 @@ const PLACEHOLDER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
 </interface>
 "#;
 
+/// Category of the source mark put on the line where rendering failed.
+const ERROR_MARK: &str = "error";
+
 // Inspired by https://gitlab.gnome.org/World/Rust/sourceview5-rs/-/blob/main/demo/src/main.rs
 
 pub struct Editor {
@@ -22,7 +27,9 @@ pub struct Editor {
 }
 
 impl Editor {
-    pub fn new<F: Fn(String) -> () + 'static>(
+    /// Creates an editor that calls `render_callback` with its text when the Render button is
+    /// clicked. If that fails with a parse error, the line it occurred on is marked.
+    pub fn new<F: Fn(String) -> crate::Result<()> + 'static>(
         parent: &impl IsA<gtk::Window>,
         starting_text: Option<String>,
         render_callback: F,
@@ -49,12 +56,40 @@ impl Editor {
         view.set_tab_width(4);
         view.set_vexpand(true);
         view.set_top_margin(6);
+        view.set_show_line_marks(true);
+
+        // The error is shown as the mark's tooltip.
+        let error_text = Rc::new(RefCell::new(String::new()));
+        let error_attributes = MarkAttributes::new();
+        error_attributes.set_icon_name("dialog-error-symbolic");
+        error_attributes.set_background(&gdk::RGBA::new(0.8, 0.1, 0.1, 0.3));
+        error_attributes.connect_query_tooltip_text(
+            clone!(@strong error_text => move |_, _| error_text.borrow().clone()),
+        );
+        view.set_mark_attributes(ERROR_MARK, &error_attributes, 0);
 
         let render = gtk::Button::with_label("Render");
-        render.connect_clicked(clone!(@weak view => move |_| {
-            let buffer = view.buffer();
+        render.connect_clicked(clone!(@weak view, @weak buffer => move |_| {
+            buffer.remove_source_marks(&buffer.start_iter(), &buffer.end_iter(), Some(ERROR_MARK));
             let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
-            render_callback(text.to_string());
+            let err = match render_callback(text.to_string()) {
+                Ok(()) => return,
+                Err(err) => err,
+            };
+            println!("Failed to render: {}", err);
+            if let crate::error::Error::ParseError { line, column, .. } = err {
+                // Buffer lines and offsets start at 0, not 1.
+                let (line, column) = (line as i32 - 1, column as i32 - 1);
+                if let Some(iter) = buffer
+                    .iter_at_line_offset(line, column)
+                    .or_else(|| buffer.iter_at_line(line))
+                {
+                    let mark = buffer.create_source_mark(None, ERROR_MARK, &iter);
+                    buffer.place_cursor(&iter);
+                    view.scroll_mark_onscreen(&mark);
+                }
+            }
+            *error_text.borrow_mut() = err.to_string();
         }));
 
         let container = gtk::Box::new(gtk::Orientation::Vertical, 0);
@@ -75,6 +110,6 @@ impl Editor {
     }
 
     pub fn show(&self) {
-        self.window.show();
+        self.window.present();
     }
 }
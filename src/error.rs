@@ -7,9 +7,18 @@ pub enum Error {
     IoError(std::io::Error),
     AddrParseError(std::net::AddrParseError),
     HttpError(reqwest::Error),
+    HttpStatusError(reqwest::StatusCode),
     GlibError(glib::error::Error),
     XmlError(quick_xml::Error),
     XmlAttrError(quick_xml::events::attributes::AttrError),
+    /// An error in a UI definition, at a 1-based line and column of its source.
+    ParseError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    JsonError(serde_json::Error),
+    CookieStoreError(Box<dyn std::error::Error + Send + Sync>),
     FromUtf8Error(std::string::FromUtf8Error),
     NoConversionError,
     HeaderToStrError(reqwest::header::ToStrError),
@@ -17,7 +26,15 @@ pub enum Error {
     NoContentTypeError,
     UnsupportedCharsetError(String),
     UnsupportedContentTypeError(String),
+    UnknownFileTypeError(String),
+    LocalFileNotAllowedError(String),
+    UnknownPageError(String),
     PropertyNotFound(String),
+    SignalNotFound(String),
+    UnknownClassError(String),
+    TemplateNotFound(String),
+    TopLevelWidgetCountError(usize),
+    NotAChildError,
 }
 
 impl fmt::Display for Error {
@@ -27,9 +44,17 @@ impl fmt::Display for Error {
             Error::IoError(err) => write!(f, "io error: {}", err),
             Error::AddrParseError(err) => write!(f, "address parse error: {}", err),
             Error::HttpError(err) => write!(f, "http error: {}", err),
+            Error::HttpStatusError(status) => write!(f, "server responded with status: {}", status),
             Error::GlibError(err) => write!(f, "glib error: {}", err),
             Error::XmlError(err) => write!(f, "xml error: {}", err),
             Error::XmlAttrError(err) => write!(f, "xml attribute error: {}", err),
+            Error::ParseError {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            Error::JsonError(err) => write!(f, "json error: {}", err),
+            Error::CookieStoreError(err) => write!(f, "cookie store error: {}", err),
             Error::FromUtf8Error(err) => write!(f, "from utf8 error: {}", err),
             Error::NoConversionError => write!(f, "no conversion error"),
             Error::HeaderToStrError(err) => {
@@ -43,7 +68,21 @@ impl fmt::Display for Error {
             Error::UnsupportedContentTypeError(content_type) => {
                 write!(f, "unsupported Content-Type: {}", content_type)
             }
+            Error::UnknownFileTypeError(path) => {
+                write!(f, "unable to determine content type of file: {}", path)
+            }
+            Error::LocalFileNotAllowedError(url) => {
+                write!(f, "only local pages can load local files: {}", url)
+            }
+            Error::UnknownPageError(location) => write!(f, "no such page: {}", location),
             Error::PropertyNotFound(name) => write!(f, "Property not found: {}", &name),
+            Error::SignalNotFound(name) => write!(f, "Signal not found: {}", &name),
+            Error::UnknownClassError(name) => write!(f, "unknown widget class: {}", name),
+            Error::TemplateNotFound(id) => write!(f, "Template not found: {}", &id),
+            Error::TopLevelWidgetCountError(count) => {
+                write!(f, "expected exactly one top-level widget, found {}", count)
+            }
+            Error::NotAChildError => write!(f, "widget is not a child of the container"),
         }
     }
 }
@@ -86,6 +125,12 @@ impl From<quick_xml::events::attributes::AttrError> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::JsonError(err)
+    }
+}
+
 impl From<std::string::FromUtf8Error> for Error {
     fn from(err: std::string::FromUtf8Error) -> Error {
         Error::FromUtf8Error(err)
@@ -1,48 +1,191 @@
-const MAX_SIZE: usize = 256;
+use std::collections::HashMap;
 
+/// The most entries a history keeps. Once it's full, the oldest entries are dropped.
+pub const MAX_SIZE: usize = 256;
+
+/// A page that was visited, along with what's needed to show it again the way it was left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub location: String,
+    pub title: Option<String>,
+    /// Horizontal and vertical scroll position of the page when it was last navigated away from.
+    pub scroll: (f64, f64),
+    /// Values of the page's input widgets when it was last navigated away from, by builder ID.
+    pub widgets: HashMap<String, WidgetValue>,
+}
+
+/// The value of an input widget, such as the text in an Entry or whether a Switch is on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WidgetValue {
+    Text(String),
+    Active(bool),
+    Number(f64),
+    Selected(u32),
+}
+
+/// A tab's navigation history, which works like a web browser's: navigating somewhere new after
+/// going back discards the entries that could have been gone forward to.
 pub struct History {
-    list: Vec<String>,
+    list: Vec<Entry>,
     cursor: Option<usize>,
 }
 
 impl History {
     pub fn new() -> Self {
         History {
-            list: Vec::with_capacity(MAX_SIZE as usize),
+            list: Vec::new(),
             cursor: None,
         }
     }
 
-    pub fn push(&mut self, addr: String) {
-        self.list.push(addr);
+    /// Adds an entry after the current one, which becomes the new current entry.
+    pub fn push(&mut self, location: String, title: Option<String>) {
+        if let Some(cursor) = self.cursor {
+            self.list.truncate(cursor + 1);
+        }
+        self.list.push(Entry {
+            location,
+            title,
+            scroll: (0.0, 0.0),
+            widgets: HashMap::new(),
+        });
+        if self.list.len() > MAX_SIZE {
+            self.list.drain(..self.list.len() - MAX_SIZE);
+        }
         self.cursor = Some(self.list.len() - 1);
     }
 
     pub fn can_go_back(&self) -> bool {
-        self.cursor.is_some() && self.cursor.unwrap() > 0
+        matches!(self.cursor, Some(cursor) if cursor > 0)
     }
 
-    pub fn back(&mut self) -> String {
+    /// Moves to the previous entry and returns it, or returns None if there isn't one.
+    pub fn back(&mut self) -> Option<&Entry> {
         if !self.can_go_back() {
-            panic!("can't go back!");
+            return None;
         }
-        self.cursor = Some(self.cursor.unwrap() - 1);
-        self.current_location()
+        let cursor = self.cursor? - 1;
+        self.cursor = Some(cursor);
+        self.list.get(cursor)
     }
 
     pub fn can_go_forward(&self) -> bool {
-        self.cursor.is_some() && self.cursor.unwrap() < (self.list.len() - 1)
+        matches!(self.cursor, Some(cursor) if cursor + 1 < self.list.len())
     }
 
-    pub fn forward(&mut self) -> String {
+    /// Moves to the next entry and returns it, or returns None if there isn't one.
+    pub fn forward(&mut self) -> Option<&Entry> {
         if !self.can_go_forward() {
-            panic!("can't go forward!");
+            return None;
         }
-        self.cursor = Some(self.cursor.unwrap() + 1);
-        self.current_location()
+        let cursor = self.cursor? + 1;
+        self.cursor = Some(cursor);
+        self.list.get(cursor)
     }
 
-    fn current_location(&self) -> String {
-        self.list.get(self.cursor.unwrap()).unwrap().clone()
+    pub fn current(&self) -> Option<&Entry> {
+        self.list.get(self.cursor?)
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Entry> {
+        self.list.get_mut(self.cursor?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn locations(history: &History) -> Vec<&str> {
+        history
+            .list
+            .iter()
+            .map(|entry| entry.location.as_str())
+            .collect()
+    }
+
+    #[test]
+    pub fn test_back_forward() {
+        let mut history = History::new();
+        assert!(history.current().is_none());
+        assert!(history.back().is_none());
+        assert!(history.forward().is_none());
+
+        history.push(String::from("a"), None);
+        history.push(String::from("b"), None);
+        assert!(history.can_go_back());
+        assert!(!history.can_go_forward());
+        assert!(history.forward().is_none());
+
+        assert_eq!(history.back().map(|entry| entry.location.as_str()), Some("a"));
+        assert!(history.back().is_none());
+        assert_eq!(history.current().map(|entry| entry.location.as_str()), Some("a"));
+        assert_eq!(history.forward().map(|entry| entry.location.as_str()), Some("b"));
+        assert!(history.forward().is_none());
+    }
+
+    #[test]
+    pub fn test_push_discards_forward_entries() {
+        let mut history = History::new();
+        history.push(String::from("a"), None);
+        history.push(String::from("b"), None);
+        history.push(String::from("c"), None);
+        history.back();
+        history.back();
+        history.push(String::from("d"), None);
+        assert_eq!(locations(&history), vec!["a", "d"]);
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back().map(|entry| entry.location.as_str()), Some("a"));
+    }
+
+    #[test]
+    pub fn test_max_size() {
+        let mut history = History::new();
+        for i in 0..MAX_SIZE + 10 {
+            history.push(i.to_string(), None);
+        }
+        assert_eq!(history.list.len(), MAX_SIZE);
+        assert_eq!(history.list[0].location, "10");
+        assert_eq!(
+            history.current().map(|entry| entry.location.clone()),
+            Some((MAX_SIZE + 9).to_string())
+        );
+
+        let mut count = 0;
+        while history.back().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, MAX_SIZE - 1);
+    }
+
+    #[test]
+    pub fn test_entry_state() {
+        let mut history = History::new();
+        history.push(String::from("a"), Some(String::from("A")));
+        history.current_mut().unwrap().scroll = (0.0, 120.0);
+        history
+            .current_mut()
+            .unwrap()
+            .widgets
+            .insert(String::from("name"), WidgetValue::Text(String::from("Webby")));
+        history.push(String::from("b"), None);
+        history.current_mut().unwrap().title = Some(String::from("B"));
+
+        assert_eq!(
+            history.back(),
+            Some(&Entry {
+                location: String::from("a"),
+                title: Some(String::from("A")),
+                scroll: (0.0, 120.0),
+                widgets: HashMap::from([(
+                    String::from("name"),
+                    WidgetValue::Text(String::from("Webby"))
+                )]),
+            })
+        );
+        assert_eq!(
+            history.forward().and_then(|entry| entry.title.clone()),
+            Some(String::from("B"))
+        );
     }
 }
@@ -2,30 +2,36 @@ use glib::{clone, ExitCode};
 use gtk::gio::Cancellable;
 use gtk::prelude::*;
 use gtk::{gdk, gio, glib};
-use mlua::prelude::*;
 use std::cell::RefCell;
 use std::fs::File;
 use std::io::Read;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+mod about;
 mod actions;
+mod bookmarks;
+mod cookie_manager;
+mod cookies;
 mod editor;
 mod error;
 mod headers;
 mod history;
+mod resource;
 mod script;
+mod tab;
 mod ui;
 mod util;
+mod visits;
 mod webdriver;
 mod window;
 
 type Result<T> = core::result::Result<T, error::Error>;
 
-pub struct Globals {
-    root_certs: Vec<reqwest::tls::Certificate>,
-    lua: Lua,
-}
+/// How often changes to the cookies and browsing history are saved while Webby is running.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
 
 fn load_cert(path: &str) -> Result<reqwest::tls::Certificate> {
     let mut buf = Vec::new();
@@ -71,6 +77,10 @@ fn main() -> Result<ExitCode> {
     let root_certs = Rc::new(RefCell::new(vec![]));
     let file_monitors = Rc::new(RefCell::new(vec![]));
     let webdriver_listeners = Rc::new(RefCell::new(vec![]));
+    let bookmarks: bookmarks::SharedBookmarks = Arc::new(Mutex::new(load_bookmarks()));
+    let bookmarks_menu = gio::Menu::new();
+    let cookie_jar = Arc::new(load_cookie_jar());
+    let visits: visits::SharedVisits = Arc::new(Mutex::new(load_visits()));
 
     app.connect_handle_local_options(
         clone!(@strong windows, @strong root_certs => move |_app, dict| {
@@ -125,7 +135,7 @@ fn main() -> Result<ExitCode> {
         }),
     );
 
-    app.connect_startup(|app| {
+    app.connect_startup(clone!(@strong bookmarks, @strong bookmarks_menu, @strong cookie_jar, @strong visits => move |app| {
         println!("app startup");
 
         let provider = gtk::CssProvider::new();
@@ -140,18 +150,32 @@ fn main() -> Result<ExitCode> {
         if let Err(err) = app.register(Cancellable::NONE) {
             println!("Failed to register appplication: {}", err);
         }
-        app.set_menubar(Some(&build_menu()));
+        window::update_bookmarks_menu(&bookmarks_menu, &bookmarks.lock().unwrap());
+        app.set_menubar(Some(&build_menu(&bookmarks_menu)));
         define_app_actions(&app);
-    });
+
+        glib::timeout_add_local(SAVE_INTERVAL, clone!(@strong cookie_jar, @strong visits => move || {
+            save_cookies(&cookie_jar);
+            save_visits(&visits);
+            glib::Continue(true)
+        }));
+    }));
+
+    app.connect_shutdown(clone!(@strong cookie_jar, @strong visits => move |_| {
+        save_cookies(&cookie_jar);
+        save_visits(&visits);
+    }));
 
     app.connect_activate(move |app| {
         println!("app activate");
-        // glib callbacks need referenced values to be 'static.
-        let globals = Box::leak(Box::new(Globals {
-            root_certs: root_certs.borrow().clone(),
-            lua: Lua::new(),
-        }));
-        let window = window::Window::new(app, globals);
+        let window = window::Window::new(
+            app,
+            &root_certs.borrow(),
+            bookmarks.clone(),
+            bookmarks_menu.clone(),
+            cookie_jar.clone(),
+            visits.clone(),
+        );
         windows.lock().unwrap().push(window);
     });
 
@@ -169,26 +193,119 @@ fn define_app_actions(app: &gtk::Application) {
     let about = gio::SimpleAction::new("about", None);
     about.connect_activate(actions::about);
     app.add_action(&about);
+
+    app.set_accels_for_action("win.new-tab", &["<Control>t"]);
+    app.set_accels_for_action("win.close-tab", &["<Control>w"]);
+}
+
+/// Returns the directory that bookmarks, cookies and history are saved in.
+fn data_dir() -> PathBuf {
+    glib::user_data_dir().join("webby")
 }
 
-fn build_menu() -> gio::Menu {
+/// Loads the user's bookmarks from their data directory.
+fn load_bookmarks() -> bookmarks::Bookmarks {
+    let path = data_dir().join("bookmarks.json");
+    match bookmarks::Bookmarks::load(path) {
+        Ok(bookmarks) => bookmarks,
+        Err(err) => {
+            // Don't save anything this session, so that the file isn't overwritten.
+            println!("Failed to load bookmarks: {}", err);
+            bookmarks::Bookmarks::default()
+        }
+    }
+}
+
+/// Loads the cookie jar shared by every window from the user's data directory.
+fn load_cookie_jar() -> cookies::Jar {
+    match cookies::Jar::load(data_dir().join("cookies.json")) {
+        Ok(jar) => jar,
+        Err(err) => {
+            // Don't save anything this session, so that the file isn't overwritten.
+            println!("Failed to load cookies: {}", err);
+            cookies::Jar::default()
+        }
+    }
+}
+
+/// Saves any cookies that have been set since they were last saved.
+fn save_cookies(cookie_jar: &cookies::Jar) {
+    if let Err(err) = cookie_jar.save_changes() {
+        println!("Failed to save cookies: {}", err);
+    }
+}
+
+/// Loads the history of visited pages shared by every window from the user's data directory.
+fn load_visits() -> visits::Visits {
+    match visits::Visits::load(data_dir().join("history.json")) {
+        Ok(visits) => visits,
+        Err(err) => {
+            // Don't save anything this session, so that the file isn't overwritten.
+            println!("Failed to load history: {}", err);
+            visits::Visits::default()
+        }
+    }
+}
+
+/// Saves any changes to the browsing history.
+fn save_visits(visits: &visits::SharedVisits) {
+    if let Err(err) = visits.lock().unwrap().save_changes() {
+        println!("Failed to save history: {}", err);
+    }
+}
+
+/// Builds the menu bar. `bookmarks_menu` is filled in with the user's bookmarks separately, so
+/// that it can be updated when they change.
+fn build_menu(bookmarks_menu: &gio::Menu) -> gio::Menu {
     let file = gio::Menu::new();
+    let new_tab = gio::MenuItem::new(Some("New Tab"), Some("win.new-tab"));
+    let close_tab = gio::MenuItem::new(Some("Close Tab"), Some("win.close-tab"));
     let open_source_editor =
         gio::MenuItem::new(Some("Open Source Editor"), Some("win.open-source-editor"));
     let quit = gio::MenuItem::new(Some("Quit"), Some("app.quit"));
+    let open_cookie_manager =
+        gio::MenuItem::new(Some("Manage Cookies"), Some("win.open-cookie-manager"));
+    let history = about_menu_item("History", "about:history");
+    let settings = about_menu_item("Settings", "about:settings");
+    file.append_item(&new_tab);
+    file.append_item(&close_tab);
+    file.append_item(&history);
+    file.append_item(&settings);
     file.append_item(&open_source_editor);
+    file.append_item(&open_cookie_manager);
     file.append_item(&quit);
 
+    let manage_bookmarks = gio::Menu::new();
+    let import_bookmarks =
+        gio::MenuItem::new(Some("Import Bookmarks..."), Some("win.import-bookmarks"));
+    let export_bookmarks =
+        gio::MenuItem::new(Some("Export Bookmarks..."), Some("win.export-bookmarks"));
+    manage_bookmarks.append_item(&about_menu_item("Manage Bookmarks", "about:bookmarks"));
+    manage_bookmarks.append_item(&import_bookmarks);
+    manage_bookmarks.append_item(&export_bookmarks);
+
+    let bookmarks = gio::Menu::new();
+    bookmarks.append_section(None, bookmarks_menu);
+    bookmarks.append_section(None, &manage_bookmarks);
+
     let help = gio::Menu::new();
     let about = gio::MenuItem::new(Some("About"), Some("app.about"));
     help.append_item(&about);
 
     let menu = gio::Menu::new();
     menu.append_submenu(Some("File"), &file);
+    menu.append_submenu(Some("Bookmarks"), &bookmarks);
     menu.append_submenu(Some("Help"), &help);
     menu
 }
 
+/// Returns a menu item that opens one of Webby's built-in pages in the current tab.
+fn about_menu_item(label: &str, location: &str) -> gio::MenuItem {
+    let item = gio::MenuItem::new(Some(label), None);
+    item.set_action_and_target_value(Some("win.go"), Some(&location.to_variant()));
+    item
+}
+
 fn watch_path(
     windows: Arc<Mutex<Vec<Arc<window::Window>>>>,
     path: &str,
@@ -209,7 +326,7 @@ fn watch_path(
             }
             let windows = windows.lock().unwrap();
             for window in windows.iter() {
-                window.clone().reload();
+                window.reload_all();
             }
         }
         _ => (),
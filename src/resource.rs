@@ -0,0 +1,304 @@
+use reqwest::blocking::multipart;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A document retrieved from some location, ready to be rendered.
+pub struct Resource {
+    /// The final URL of the resource, after following any redirects.
+    pub url: String,
+    pub content_type: mime::Mime,
+    pub body: String,
+}
+
+impl Resource {
+    /// Reads a resource from an HTTP response. This blocks until the full body has been received,
+    /// so it should be called off of the main thread.
+    pub fn from_response(mut response: reqwest::blocking::Response) -> crate::Result<Self> {
+        let url = response.url().to_string();
+
+        // Servers can send their own error pages as UI definitions, which are shown like any other
+        // page. Anything else is replaced with one of Webby's error pages.
+        if !response.status().is_success() && !has_gtk_body(&response) {
+            return Err(crate::error::Error::HttpStatusError(response.status()));
+        }
+
+        let content_type: mime::Mime = match response.headers().get(reqwest::header::CONTENT_TYPE) {
+            Some(content_type) => content_type.to_str()?.parse()?,
+            None => return Err(crate::error::Error::NoContentTypeError),
+        };
+
+        if let Some(charset) = content_type.get_param("charset") {
+            if charset.as_str() != "utf-8" {
+                return Err(crate::error::Error::UnsupportedCharsetError(
+                    charset.as_str().into(),
+                ));
+            }
+        }
+
+        let mut body = String::new();
+        response.read_to_string(&mut body)?;
+
+        Ok(Resource {
+            url,
+            content_type,
+            body,
+        })
+    }
+
+    /// Reads a resource from the local filesystem. Since there are no headers to go by, the
+    /// content type is inferred from the file extension.
+    pub fn from_file(url: String, path: &Path) -> crate::Result<Self> {
+        let content_type = content_type_for_path(path)?;
+        let body = std::fs::read_to_string(path)?;
+        Ok(Resource {
+            url,
+            content_type,
+            body,
+        })
+    }
+
+    /// Loads one of Webby's built-in pages, like `about:history`.
+    pub fn from_about(url: String) -> crate::Result<Self> {
+        let body = crate::about::source(&url)?.to_string();
+        Ok(Resource {
+            url,
+            content_type: "application/gtk".parse()?,
+            body,
+        })
+    }
+}
+
+/// Loads the text of a file referenced by the page at `page_url`, like a script or stylesheet.
+/// Unlike pages, these aren't checked for a content type. Only pages that were loaded from the
+/// local filesystem themselves can reference local files. This blocks, so it should be called off
+/// of the main thread.
+pub fn fetch_text(
+    http_client: &reqwest::blocking::Client,
+    page_url: &str,
+    url: &str,
+) -> crate::Result<String> {
+    if let Some(path) = crate::util::file_path(url) {
+        if crate::util::file_path(page_url).is_none() {
+            return Err(crate::error::Error::LocalFileNotAllowedError(url.to_string()));
+        }
+        return Ok(std::fs::read_to_string(path)?);
+    }
+
+    let response = http_client.get(url).send()?;
+    if !response.status().is_success() {
+        return Err(crate::error::Error::HttpStatusError(response.status()));
+    }
+    Ok(response.text()?)
+}
+
+/// Builds a multipart/form-data body out of form values and files to upload, giving each file the
+/// content type that its name suggests. As the files are read to be sent, `on_progress` is called
+/// with how many bytes of them have been read and the total, each time another percent has been.
+/// This opens the files, so it should be called off of the main thread.
+pub fn multipart_form<F>(
+    values: HashMap<String, String>,
+    files: Vec<(String, PathBuf)>,
+    on_progress: F,
+) -> crate::Result<multipart::Form>
+where
+    F: Fn(u64, u64) + Send + Sync + 'static,
+{
+    let mut form = multipart::Form::new();
+    for (name, value) in values {
+        form = form.text(name, value);
+    }
+
+    // Every file is opened up front, so that the total size is known before any are sent.
+    let mut opened = Vec::new();
+    let mut total = 0;
+    for (name, path) in files {
+        let file = File::open(&path)?;
+        let length = file.metadata()?.len();
+        total += length;
+        opened.push((name, path, file, length));
+    }
+
+    let progress = Arc::new(Progress {
+        read: AtomicU64::new(0),
+        total,
+        on_progress,
+    });
+    for (name, path, file, length) in opened {
+        let reader = ProgressReader {
+            file,
+            progress: progress.clone(),
+        };
+        let mut part = multipart::Part::reader_with_length(reader, length)
+            .mime_str(upload_content_type(&path).as_ref())?;
+        if let Some(file_name) = path.file_name() {
+            part = part.file_name(file_name.to_string_lossy().into_owned());
+        }
+        form = form.part(name, part);
+    }
+    Ok(form)
+}
+
+/// How much of the files in a multipart body have been read.
+struct Progress<F> {
+    read: AtomicU64,
+    total: u64,
+    on_progress: F,
+}
+
+/// A file being uploaded, which reports its progress as it's read.
+struct ProgressReader<F> {
+    file: File,
+    progress: Arc<Progress<F>>,
+}
+
+impl<F: Fn(u64, u64)> Read for ProgressReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read(buf)?;
+        let progress = &self.progress;
+        let before = progress.read.fetch_add(n as u64, Ordering::Relaxed);
+        let after = before + n as u64;
+        if progress.total > 0 && before * 100 / progress.total != after * 100 / progress.total {
+            (progress.on_progress)(after, progress.total);
+        }
+        Ok(n)
+    }
+}
+
+/// Returns the content type that a file is uploaded with, based on its extension.
+fn upload_content_type(path: &Path) -> mime::Mime {
+    mime_guess::from_path(path).first_or_octet_stream()
+}
+
+/// Returns true if the response carries a UI definition that can be rendered directly.
+fn has_gtk_body(response: &reqwest::blocking::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.parse::<mime::Mime>().ok())
+        .map_or(false, |mime_type| is_gtk(&mime_type))
+}
+
+pub fn is_gtk(mime_type: &mime::Mime) -> bool {
+    match mime_type.type_() {
+        mime::TEXT | mime::APPLICATION => mime_type.subtype() == "gtk",
+        _ => false,
+    }
+}
+
+fn content_type_for_path(path: &Path) -> crate::Result<mime::Mime> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("ui") => Ok("application/gtk".parse()?),
+        Some("txt") => Ok(mime::TEXT_PLAIN),
+        _ => Err(crate::error::Error::UnknownFileTypeError(
+            path.display().to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_content_type_for_path() -> crate::Result<()> {
+        assert_eq!(
+            content_type_for_path(Path::new("/tmp/index.ui"))?.essence_str(),
+            "application/gtk"
+        );
+        assert_eq!(
+            content_type_for_path(Path::new("notes.txt"))?,
+            mime::TEXT_PLAIN
+        );
+        assert!(content_type_for_path(Path::new("/tmp/image.png")).is_err());
+        assert!(content_type_for_path(Path::new("/tmp/Makefile")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_multipart_form() -> crate::Result<()> {
+        let dir = std::env::temp_dir().join(format!("webby-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let first = dir.join("notes.txt");
+        let second = dir.join("photo.png");
+        std::fs::write(&first, vec![b'a'; 300])?;
+        std::fs::write(&second, vec![b'b'; 700])?;
+
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let form = multipart_form(
+            HashMap::from([(String::from("title"), String::from("Trip"))]),
+            vec![
+                (String::from("attachment"), first),
+                (String::from("attachment"), second),
+            ],
+            {
+                let reports = reports.clone();
+                move |read, total| reports.lock().unwrap().push((read, total))
+            },
+        )?;
+        let boundary = form.boundary().to_string();
+
+        // Send the form to a local server, to see the body as a server would.
+        let server = tiny_http::Server::http("127.0.0.1:0")
+            .map_err(|err| crate::error::Error::Any(err.to_string()))?;
+        let url = format!("http://{}/upload", server.server_addr());
+        let received = std::thread::spawn(move || -> std::io::Result<String> {
+            let mut request = server.recv()?;
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            request.respond(tiny_http::Response::empty(200))?;
+            Ok(body)
+        });
+        reqwest::blocking::Client::new()
+            .post(&url)
+            .multipart(form)
+            .send()?;
+        let body = received.join().unwrap()?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert!(body.contains(&format!("--{}\r\n", boundary)));
+        assert!(body.contains("name=\"title\"\r\n\r\nTrip\r\n"));
+        assert!(body.contains(
+            "name=\"attachment\"; filename=\"notes.txt\"\r\nContent-Type: text/plain\r\n"
+        ));
+        assert!(body.contains(
+            "name=\"attachment\"; filename=\"photo.png\"\r\nContent-Type: image/png\r\n"
+        ));
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.last(), Some(&(1000, 1000)));
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_is_gtk() -> crate::Result<()> {
+        assert!(is_gtk(&"application/gtk".parse()?));
+        assert!(is_gtk(&"text/gtk; charset=utf-8".parse()?));
+        assert!(!is_gtk(&mime::TEXT_PLAIN));
+        assert!(!is_gtk(&mime::APPLICATION_JSON));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_fetch_text_from_file() -> crate::Result<()> {
+        let path = std::env::temp_dir().join("webby-test-fetch-text.lua");
+        std::fs::write(&path, "print('hi')")?;
+        let url = format!("file://{}", path.display());
+        let http_client = reqwest::blocking::Client::new();
+
+        assert_eq!(
+            fetch_text(&http_client, "file:///tmp/index.ui", &url)?,
+            "print('hi')"
+        );
+        assert!(matches!(
+            fetch_text(&http_client, "https://example.com/index.ui", &url),
+            Err(crate::error::Error::LocalFileNotAllowedError(_))
+        ));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -3,58 +3,141 @@ use mlua::prelude::*;
 
 use glib::signal::SignalHandlerId;
 use glib::{Continue, MainContext, PRIORITY_DEFAULT};
-use gtk::glib;
+use gtk::{gdk, glib};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
 use std::sync::Arc;
 
-pub fn init(window: Arc<crate::window::Window>) {
-    let globals = window.state.lock().unwrap().globals;
-    let lua = &globals.lua;
+/// Creates a fresh Lua runtime for a page, with the global functions registered. Privileged pages
+/// also get the functions used by Webby's built-in pages.
+pub fn new(tab: Arc<crate::tab::Tab>, privileged: bool) -> Rc<Lua> {
+    let lua = Rc::new(Lua::new());
+    // Callbacks that outlive the current call, such as signal handlers, only hold on to a weak
+    // reference, so that they stop working once the page is unloaded.
+    lua.set_app_data(Rc::downgrade(&lua));
+    lua.set_app_data(SignalHandlers::default());
+    lua.set_app_data(BuiltObjects::default());
 
-    let r#do = move || -> LuaResult<()> {
-        for (name, function) in global_functions(lua, window.clone())? {
+    let r#do = || -> LuaResult<()> {
+        for (name, function) in global_functions(&lua, tab.clone())? {
             lua.globals().set(name, function)?;
         }
-        lua.globals().set(
-            super::WINDOW,
-            Window {
-                globals,
-                window: window.clone(),
-            },
-        )?;
+        if privileged {
+            for (name, function) in privileged_functions(&lua, tab.clone())? {
+                lua.globals().set(name, function)?;
+            }
+        }
+        lua.globals().set(super::WINDOW, Window { tab })?;
         Ok(())
     };
 
     if let Err(err) = r#do() {
         println!("Failed to register lua globals: {}", err);
     }
+    lua
 }
 
-fn global_functions(
-    lua: &'static Lua,
-    window: Arc<crate::window::Window>,
-) -> LuaResult<HashMap<&'static str, LuaFunction>> {
+/// Runs the page's `on_unload` hook, if it defined one, and then disconnects every signal handler
+/// that its scripts connected.
+pub fn unload(lua: &Lua) {
+    match lua.globals().get::<_, Option<LuaFunction>>(super::ON_UNLOAD) {
+        Ok(Some(on_unload)) => {
+            if let Err(err) = on_unload.call::<_, ()>(()) {
+                println!("Error calling {}: {}", super::ON_UNLOAD, err);
+            }
+        }
+        Ok(None) => (),
+        Err(err) => println!("{} is not a function: {}", super::ON_UNLOAD, err),
+    }
+
+    if let Some(mut handlers) = lua.app_data_mut::<SignalHandlers>() {
+        for (object, signal_id) in handlers.0.drain(..) {
+            // Objects that have already been freed took their handlers with them.
+            if let Some(object) = object.upgrade() {
+                object.disconnect(signal_id);
+            }
+        }
+    }
+
+    // The disconnected handlers owned their callbacks' registry keys, so free those values now.
+    lua.expire_registry_values();
+}
+
+/// Signal handlers connected by a page's scripts. The objects are only weakly referenced, so that
+/// connecting to a widget doesn't keep it alive after it's been removed from the page.
+#[derive(Default)]
+struct SignalHandlers(Vec<(glib::WeakRef<glib::Object>, SignalHandlerId)>);
+
+/// Objects with IDs that the page's scripts built after the page was loaded, by `build_widget` or
+/// `clone_widget`. These are kept out of the page's builder, which would hold on to them for as
+/// long as the page is shown, so only weak references are kept and the objects are freed once
+/// they're no longer in use.
+#[derive(Default)]
+struct BuiltObjects {
+    objects: HashMap<String, glib::WeakRef<glib::Object>>,
+    /// How many copies of each template have been made, for numbering the next one.
+    copies: HashMap<String, usize>,
+    /// How many objects there can be before the ones that have been freed are forgotten.
+    prune_at: usize,
+}
+
+impl BuiltObjects {
+    fn get(&self, id: &str) -> Option<glib::Object> {
+        self.objects.get(id).and_then(|object| object.upgrade())
+    }
+
+    fn insert(&mut self, id: String, object: &glib::Object) {
+        if self.objects.len() >= self.prune_at {
+            self.objects.retain(|_, object| object.upgrade().is_some());
+            self.prune_at = (self.objects.len() * 2).max(64);
+        }
+        self.objects.insert(id, object.downgrade());
+    }
+}
+
+/// Returns a weak reference to the runtime, for use by callbacks that are invoked later.
+fn weak_handle(lua: &Lua) -> Weak<Lua> {
+    lua.app_data_ref::<Weak<Lua>>()
+        .map(|handle| handle.clone())
+        .unwrap_or_default()
+}
+
+fn global_functions<'lua>(
+    lua: &'lua Lua,
+    tab: Arc<crate::tab::Tab>,
+) -> LuaResult<HashMap<&'static str, LuaFunction<'lua>>> {
     let mut functions = HashMap::new();
 
     {
-        let window = window.clone();
+        let tab = tab.clone();
         functions.insert(
             super::ALERT,
             lua.create_function(move |_, text: String| {
-                window.clone().alert(&text);
+                tab.clone().alert(&text);
                 Ok(())
             })?,
         );
     }
 
     {
-        let window = window.clone();
+        let tab = tab.clone();
         functions.insert(
             super::FIND_WIDGET,
-            lua.create_function(move |_, id: String| {
-                match window.state.lock().unwrap().builder.object::<gtk::Widget>(&id) {
-                    // TODO: need to figure out how to drop this widget after it's no longer visible
-                    Some(widget) => Ok(Some(Widget::new(lua, widget))),
+            lua.create_function(move |lua, id: String| {
+                let widget = tab
+                    .state
+                    .lock()
+                    .unwrap()
+                    .builder
+                    .object::<gtk::Widget>(&id)
+                    .or_else(|| {
+                        lua.app_data_ref::<BuiltObjects>()
+                            .and_then(|built| built.get(&id))
+                            .and_then(|object| object.downcast::<gtk::Widget>().ok())
+                    });
+                match widget {
+                    Some(widget) => Ok(Some(Widget::new(widget))),
                     None => {
                         println!("No widget found with id: {}", &id);
                         Ok(None)
@@ -65,11 +148,17 @@ fn global_functions(
     }
 
     {
-        let window = window.clone();
+        let tab = tab.clone();
         functions.insert(
             super::SUBMIT_FORM,
             lua.create_function(
-                move |_, (method, action, values): (String, String, LuaTable)| {
+                move |lua,
+                      (method, action, values, callback): (
+                    String,
+                    String,
+                    LuaTable,
+                    Option<LuaFunction>,
+                )| {
                     let method = match reqwest::Method::from_bytes(method.as_bytes()) {
                         Ok(method) => method,
                         Err(err) => {
@@ -78,32 +167,39 @@ fn global_functions(
                     };
 
                     let mut form_values = HashMap::new();
+                    let mut files = Vec::new();
                     // TODO: automatically convert other types, like boolean?
-                    for pair in values.pairs::<String, String>() {
+                    for pair in values.pairs::<String, LuaValue>() {
                         let (key, value) = pair?;
-                        form_values.insert(key, value);
+                        match value {
+                            LuaValue::UserData(ud) if ud.is::<File>() => {
+                                files.push((key, ud.borrow::<File>()?.path.clone()));
+                            }
+                            // An array of files, like the one given to a choose_file callback.
+                            LuaValue::Table(table) => {
+                                for file in table.sequence_values::<LuaAnyUserData>() {
+                                    files.push((key.clone(), file?.borrow::<File>()?.path.clone()));
+                                }
+                            }
+                            value => {
+                                form_values.insert(key, lua.unpack::<String>(value)?);
+                            }
+                        }
                     }
 
-                    let http_client = &window.state.lock().unwrap().http_client;
-                    let response = match http_client
-                        .request(
-                            method,
-                            crate::util::absolutize_url(&window.state.lock().unwrap().location, &action),
-                        )
-                        .form(&form_values)
-                        .send()
-                    {
-                        Ok(response) => response,
-                        Err(err) => {
-                            return Err(LuaError::ExternalError(Arc::new(err)));
-                        }
+                    let callback_key = match callback {
+                        Some(callback) => Some(lua.create_registry_value(callback)?),
+                        None => None,
                     };
-
-                    if response.status().is_success() {
-                        window.clone().reload();
-                    } else if response.status().is_redirection() {
-                        println!("TODO: Need to redirect, probably to {}", response.url());
-                    }
+                    let lua_handle = weak_handle(lua);
+                    tab.clone().submit_form(method, action, form_values, files, move |err| {
+                        if let (Some(callback_key), Some(lua)) = (callback_key, lua_handle.upgrade()) {
+                            let f: LuaFunction = lua.registry_value(&callback_key).unwrap();
+                            if let Err(err) = f.call::<_, ()>(err.map(|err| err.to_string())) {
+                                println!("Failed to invoke submit_form callback: {}", err);
+                            }
+                        }
+                    });
                     Ok(())
                 },
             )?,
@@ -111,11 +207,37 @@ fn global_functions(
     }
 
     {
-        let window = window.clone();
+        let tab = tab.clone();
+        functions.insert(
+            super::CHOOSE_FILE,
+            lua.create_function(move |lua, callback: LuaFunction| {
+                let callback_key = lua.create_registry_value(callback)?;
+                let lua_handle = weak_handle(lua);
+                tab.choose_files(move |paths| {
+                    let lua = match lua_handle.upgrade() {
+                        Some(lua) => lua,
+                        // The page has been unloaded since the chooser was opened.
+                        None => return,
+                    };
+                    let f: LuaFunction = lua.registry_value(&callback_key).unwrap();
+                    let files = paths.into_iter().map(File::new).collect::<Vec<_>>();
+                    if let Err(err) = f.call::<_, ()>(files) {
+                        println!("Failed to invoke choose_file callback: {}", err);
+                    }
+                });
+                Ok(())
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
         functions.insert(
             super::FETCH,
             lua.create_function(
-                move |_, (method, url, callback): (String, String, LuaFunction)| {
+                move |lua, (method, url, callback): (String, String, LuaFunction)| {
+                    // Relative URLs resolve against the page, like hrefs.
+                    let url = tab.resolve(&url);
                     if !url.contains("://") {
                         if let Err(err) = callback.call::<_, ()>((
                             format!("URL is missing protocol: {}", url),
@@ -135,7 +257,7 @@ fn global_functions(
 
                     let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
 
-                    let request = window.state.lock().unwrap().http_client.request(method, url);
+                    let request = tab.state.lock().unwrap().http_client.request(method, url);
                     std::thread::spawn(move || {
                         let response_result = request.send();
                         if let Err(err) = sender.send(response_result) {
@@ -146,7 +268,13 @@ fn global_functions(
                     let callback_key = lua
                         .create_registry_value(callback)
                         .expect("Failed to create Lua registry value");
+                    let lua_handle = weak_handle(lua);
                     receiver.attach(None, move |response_result| {
+                        let lua = match lua_handle.upgrade() {
+                            Some(lua) => lua,
+                            // The page has been unloaded since the request was made.
+                            None => return Continue(false),
+                        };
                         let f: LuaFunction = lua.registry_value(&callback_key).unwrap();
                         match response_result {
                             Ok(response) => {
@@ -174,198 +302,741 @@ fn global_functions(
         );
     }
 
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::CREATE_WIDGET,
+            lua.create_function(
+                move |lua, (class_name, properties): (String, Option<LuaTable>)| {
+                    let type_ = tab
+                        .state
+                        .lock()
+                        .unwrap()
+                        .builder
+                        .type_from_name(&class_name);
+                    if !type_.is_a(gtk::Widget::static_type()) {
+                        println!("Unknown widget class: {}", &class_name);
+                        return Err(LuaError::ExternalError(Arc::new(
+                            crate::error::Error::UnknownClassError(class_name),
+                        )));
+                    }
+
+                    let object = match std::panic::catch_unwind(|| glib::Object::with_type(type_)) {
+                        Ok(object) => object,
+                        Err(err) => {
+                            let s = format!("Failed to create {}: {:?}", &class_name, err);
+                            println!("{}", &s);
+                            return Err(LuaError::ExternalError(Arc::new(
+                                crate::error::Error::Any(s),
+                            )));
+                        }
+                    };
+
+                    if let Some(properties) = properties {
+                        for pair in properties.pairs::<String, LuaValue>() {
+                            let (name, value) = pair?;
+                            set_property(&object, &name, &value)?;
+                        }
+                    }
+                    wrap_object(lua, object)
+                },
+            )?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::BUILD_WIDGET,
+            lua.create_function(move |lua, ui: String| {
+                // Allow passing a bare <object> element rather than a whole UI definition.
+                let ui = if ui.trim_start().starts_with("<object") {
+                    format!("<interface>{}</interface>", ui)
+                } else {
+                    ui
+                };
+                let definition = crate::ui::Definition::new(ui)
+                    .map_err(|err| LuaError::ExternalError(Arc::new(err)))?;
+                let builder = build_fragment(lua, &tab, &definition.buildable, &definition.hrefs)
+                    .map_err(|err| LuaError::ExternalError(Arc::new(err)))?;
+
+                let mut roots: Vec<gtk::Widget> = builder
+                    .objects()
+                    .into_iter()
+                    .filter_map(|object| object.downcast::<gtk::Widget>().ok())
+                    .filter(|widget| widget.parent().is_none())
+                    .collect();
+                match roots.len() {
+                    1 => Ok(Widget::new(roots.remove(0))),
+                    n => Err(LuaError::ExternalError(Arc::new(
+                        crate::error::Error::TopLevelWidgetCountError(n),
+                    ))),
+                }
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::CLONE_WIDGET,
+            lua.create_function(move |lua, id: String| {
+                let (buildable, hrefs, page_builder) = {
+                    let state = tab.state.lock().unwrap();
+                    match &state.ui_definition {
+                        Some(definition) => (
+                            definition.buildable.clone(),
+                            definition.hrefs.clone(),
+                            state.builder.clone(),
+                        ),
+                        None => {
+                            return Err(LuaError::ExternalError(Arc::new(
+                                crate::error::Error::TemplateNotFound(id),
+                            )));
+                        }
+                    }
+                };
+
+                // Number the copies, skipping any IDs that the page itself uses.
+                let suffix = {
+                    let mut built = lua.app_data_mut::<BuiltObjects>().unwrap();
+                    let copies = built.copies.entry(id.clone()).or_insert(0);
+                    loop {
+                        *copies += 1;
+                        let suffix = format!("-{}", copies);
+                        if page_builder
+                            .object::<glib::Object>(&format!("{}{}", &id, suffix))
+                            .is_none()
+                        {
+                            break suffix;
+                        }
+                    }
+                };
+
+                let (ui, ids) = match crate::ui::clone_object(&buildable, &id, &suffix) {
+                    Ok(Some(clone)) => clone,
+                    Ok(None) => {
+                        println!("No template found with id: {}", &id);
+                        return Err(LuaError::ExternalError(Arc::new(
+                            crate::error::Error::TemplateNotFound(id),
+                        )));
+                    }
+                    Err(err) => return Err(LuaError::ExternalError(Arc::new(err))),
+                };
+
+                let hrefs = ids
+                    .iter()
+                    .filter_map(|(old_id, new_id)| {
+                        hrefs
+                            .get(old_id)
+                            .map(|href| (new_id.clone(), href.clone()))
+                    })
+                    .collect();
+                let builder = build_fragment(lua, &tab, &ui, &hrefs)
+                    .map_err(|err| LuaError::ExternalError(Arc::new(err)))?;
+
+                let root = match builder.object::<gtk::Widget>(&ids[&id]) {
+                    Some(root) => root,
+                    None => {
+                        return Err(LuaError::ExternalError(Arc::new(
+                            crate::error::Error::TemplateNotFound(id),
+                        )));
+                    }
+                };
+
+                // Scripts know the objects inside the template by their original IDs.
+                let objects = lua.create_table()?;
+                for (old_id, new_id) in &ids {
+                    if let Some(object) = builder.object::<glib::Object>(new_id) {
+                        objects.set(old_id.as_str(), wrap_object(lua, object)?)?;
+                    }
+                }
+                Ok((Widget::new(root), objects))
+            })?,
+        );
+    }
+
     Ok(functions)
 }
 
-#[allow(dead_code)]
-fn glib_to_lua(lua: &'static Lua, value: glib::Value) -> Option<LuaValue> {
-    println!("glib_to_lua: converting {:?}", &value);
-    use glib::types::Type;
-    let mut current_type = Some(value.type_());
-    while let Some(t) = current_type {
-        match t {
-            Type::INVALID | Type::UNIT => return Some(LuaValue::Nil), // not sure if it's possible to initialize a unit value...
-            Type::BOOL => return Some(LuaValue::Boolean(value.get().unwrap())),
-            _ => (),
-        }
-        if t == gtk::Widget::static_type() {
-            let transformed_value = match value.transform_with_type(t) {
-                Ok(v) => v,
-                Err(err) => {
-                    println!(
-                        "failed to transform value '{:?}' into type '{:?}': {}",
-                        value, t, err
-                    );
-                    return None;
+/// Functions that give access to the browser itself, like its history and bookmarks. These are
+/// only registered for Webby's built-in pages, never for pages loaded from elsewhere.
+fn privileged_functions<'lua>(
+    lua: &'lua Lua,
+    tab: Arc<crate::tab::Tab>,
+) -> LuaResult<HashMap<&'static str, LuaFunction<'lua>>> {
+    let mut functions = HashMap::new();
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::NAVIGATE,
+            lua.create_function(move |_, url: String| {
+                // Navigating unloads the current page, so it can't happen while its script is
+                // still running.
+                let tab = tab.clone();
+                glib::idle_add_local_once(move || tab.go(url, true));
+                Ok(())
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::RELOAD,
+            lua.create_function(move |_, ()| {
+                let tab = tab.clone();
+                glib::idle_add_local_once(move || tab.reload());
+                Ok(())
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::SEARCH_HISTORY,
+            lua.create_function(move |lua, (query, limit): (Option<String>, Option<usize>)| {
+                let visits = tab.visits.lock().unwrap();
+                let results = lua.create_table()?;
+                for (i, visit) in visits
+                    .search(query.as_deref().unwrap_or(""), limit.unwrap_or(usize::MAX))
+                    .into_iter()
+                    .enumerate()
+                {
+                    let table = lua.create_table()?;
+                    table.set("url", visit.url.as_str())?;
+                    table.set("title", visit.title.as_deref())?;
+                    table.set("last_visited", visit.last_visited)?;
+                    table.set("count", visit.count)?;
+                    results.set(i + 1, table)?;
                 }
-            };
-            let widget: gtk::Widget = match transformed_value.get() {
-                Ok(widget) => widget,
-                Err(err) => {
-                    println!("failed to extract value as widget: {}", err);
-                    return None;
+                Ok(results)
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::CLEAR_HISTORY,
+            lua.create_function(move |_, ()| {
+                let mut visits = tab.visits.lock().unwrap();
+                visits.clear();
+                visits
+                    .save_changes()
+                    .map_err(|err| LuaError::ExternalError(Arc::new(err)))
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::LIST_BOOKMARKS,
+            lua.create_function(move |lua, ()| {
+                let results = lua.create_table()?;
+                if let Some(window) = tab.window() {
+                    let bookmarks = window.bookmarks.lock().unwrap();
+                    for (i, bookmark) in bookmarks.list().iter().enumerate() {
+                        let table = lua.create_table()?;
+                        table.set("title", bookmark.title.as_str())?;
+                        table.set("url", bookmark.url.as_str())?;
+                        results.set(i + 1, table)?;
+                    }
+                }
+                Ok(results)
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::RENAME_BOOKMARK,
+            lua.create_function(move |_, (url, title): (String, String)| {
+                let window = match tab.window() {
+                    Some(window) => window,
+                    None => return Ok(false),
+                };
+                let renamed = window.bookmarks.lock().unwrap().rename(&url, title);
+                if renamed {
+                    window.save_bookmarks();
+                }
+                Ok(renamed)
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::REMOVE_BOOKMARK,
+            lua.create_function(move |_, url: String| {
+                let window = match tab.window() {
+                    Some(window) => window,
+                    None => return Ok(false),
+                };
+                let removed = window.bookmarks.lock().unwrap().remove(&url);
+                if removed {
+                    window.save_bookmarks();
+                }
+                Ok(removed)
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::CLEAR_COOKIES,
+            lua.create_function(move |_, ()| match tab.window() {
+                Some(window) => window
+                    .cookie_jar
+                    .clear()
+                    .map_err(|err| LuaError::ExternalError(Arc::new(err))),
+                None => Ok(()),
+            })?,
+        );
+    }
+
+    {
+        let tab = tab.clone();
+        functions.insert(
+            super::OPEN_COOKIE_MANAGER,
+            lua.create_function(move |_, ()| {
+                if let Some(window) = tab.window() {
+                    if let Err(err) = window
+                        .app_window
+                        .activate_action("win.open-cookie-manager", None)
+                    {
+                        println!("Failed to open cookie manager: {}", err);
+                    }
                 }
-            };
+                Ok(())
+            })?,
+        );
+    }
+
+    functions.insert(
+        super::VERSION_INFO,
+        lua.create_function(|lua, ()| {
+            let info = lua.create_table()?;
+            info.set("webby", env!("CARGO_PKG_VERSION"))?;
+            info.set(
+                "gtk",
+                format!(
+                    "{}.{}.{}",
+                    gtk::major_version(),
+                    gtk::minor_version(),
+                    gtk::micro_version()
+                ),
+            )?;
+            info.set("data_dir", crate::data_dir().display().to_string())?;
+            Ok(info)
+        })?,
+    );
 
-            // Okay, so putting the userdata into a table works, but using it directly in a
-            // function call doesn't...
+    Ok(functions)
+}
+
+/// Builds a fragment of UI definition for insertion into the current page. Its objects are
+/// remembered so that `find_widget` can find them, and its hrefs and links are connected.
+fn build_fragment(
+    lua: &Lua,
+    tab: &Arc<crate::tab::Tab>,
+    buildable: &str,
+    hrefs: &HashMap<String, crate::ui::Href>,
+) -> crate::Result<gtk::Builder> {
+    let page_builder = tab.state.lock().unwrap().builder.clone();
+    let builder = gtk::Builder::new();
+    builder.add_from_string(buildable)?;
+
+    if let Some(mut built) = lua.app_data_mut::<BuiltObjects>() {
+        for object in builder.objects() {
+            if let Some(id) = object
+                .dynamic_cast_ref::<gtk::Buildable>()
+                .and_then(|buildable| buildable.buildable_id())
             {
-                lua.globals()
-                    .set(
-                        "special_widget_value",
-                        Widget::new(lua, widget.clone()).to_lua(lua).unwrap(),
-                    )
-                    .unwrap();
+                // Internal IDs are generated by GTK, and existing IDs must keep pointing at their
+                // original objects for as long as those are around.
+                if id.starts_with("___")
+                    || page_builder.object::<glib::Object>(&id).is_some()
+                    || built.get(&id).is_some()
+                {
+                    continue;
+                }
+                built.insert(id.to_string(), &object);
             }
-            let lua_widget = match Widget::new(lua, widget).to_lua(lua) {
-                Ok(lua_widget) => lua_widget,
+        }
+    }
+
+    tab.clone().connect_links(&builder, hrefs);
+    Ok(builder)
+}
+
+fn glib_to_lua<'lua>(lua: &'lua Lua, value: &glib::Value) -> Option<LuaValue<'lua>> {
+    use glib::types::Type;
+    let lua_value = match value.type_() {
+        Type::INVALID | Type::UNIT => LuaValue::Nil, // not sure if it's possible to initialize a unit value...
+        Type::BOOL => LuaValue::Boolean(value.get().ok()?),
+        Type::I8 => LuaValue::Integer(value.get::<i8>().ok()?.into()),
+        Type::U8 => LuaValue::Integer(value.get::<u8>().ok()?.into()),
+        Type::I32 => LuaValue::Integer(value.get::<i32>().ok()?.into()),
+        Type::U32 => LuaValue::Integer(value.get::<u32>().ok()?.into()),
+        Type::I_LONG => LuaValue::Integer(i64::try_from(value.get::<glib::ILong>().ok()?.0).ok()?),
+        Type::U_LONG => LuaValue::Integer(i64::try_from(value.get::<glib::ULong>().ok()?.0).ok()?),
+        Type::I64 => LuaValue::Integer(value.get::<i64>().ok()?),
+        Type::U64 => {
+            let v = value.get::<u64>().ok()?;
+            i64::try_from(v).map_or(LuaValue::Number(v as f64), LuaValue::Integer)
+        }
+        Type::F32 => LuaValue::Number(value.get::<f32>().ok()?.into()),
+        Type::F64 => LuaValue::Number(value.get::<f64>().ok()?),
+        Type::STRING => match value.get::<Option<String>>().ok()? {
+            Some(s) => LuaValue::String(lua.create_string(&s).ok()?),
+            None => LuaValue::Nil,
+        },
+        t if t.is_a(Type::ENUM) => {
+            let (_, enum_value) = glib::EnumValue::from_value(value)?;
+            LuaValue::String(lua.create_string(enum_value.nick()).ok()?)
+        }
+        t if t.is_a(Type::FLAGS) => {
+            let (_, flags_values) = glib::FlagsValue::from_value(value)?;
+            let nicks: Vec<&str> = flags_values.iter().map(|v| v.nick()).collect();
+            LuaValue::String(lua.create_string(&nicks.join("|")).ok()?)
+        }
+        t if t == <Vec<String>>::static_type() => {
+            let strings: Vec<String> = value.get().ok()?;
+            LuaValue::Table(lua.create_sequence_from(strings).ok()?)
+        }
+        t if t == gdk::RGBA::static_type() => match value.get::<Option<gdk::RGBA>>().ok()? {
+            Some(rgba) => LuaValue::String(lua.create_string(rgba.to_str().as_str()).ok()?),
+            None => LuaValue::Nil,
+        },
+        t if t.is_a(Type::OBJECT) => match value.get::<Option<glib::Object>>().ok()? {
+            Some(object) => match wrap_object(lua, object) {
+                Ok(lua_object) => lua_object,
                 Err(err) => {
-                    println!("failed to convert widget into Lua value: {}", err);
+                    println!("failed to convert object into Lua value: {}", err);
                     return None;
                 }
-            };
-            return Some(lua_widget);
+            },
+            None => LuaValue::Nil,
+        },
+        _ => {
+            println!("Unimplemented glib->Lua conversion: {:?}", value);
+            return None;
         }
-        current_type = t.parent();
-    }
-    None
+    };
+    Some(lua_value)
 }
 
-fn lua_to_glib(value: &LuaValue) -> Option<glib::Value> {
+/// Converts a Lua value into a glib value of the requested type, such as the value type of the
+/// property being set.
+fn lua_to_glib(value: &LuaValue, type_: glib::Type) -> Option<glib::Value> {
+    use glib::types::Type;
     use LuaValue::*;
-    match value {
-        Nil => None,
-        Boolean(v) => Some(v.to_value()),
+    match (value, type_) {
+        // Anything that can hold a pointer can be set to NULL.
+        (Nil, t) if t.is_a(Type::OBJECT) || t.is_a(Type::BOXED) || t == Type::STRING => {
+            Some(glib::Value::from_type(t))
+        }
+        (Nil, _) => None,
+        (Boolean(v), Type::BOOL) => Some(v.to_value()),
+        (Integer(_) | Number(_), t) => number_to_glib(value, t),
+        (String(s), Type::STRING) => Some(s.to_str().ok()?.to_value()),
+        (String(s), t) if t.is_a(Type::ENUM) => {
+            glib::EnumClass::new(t)?.to_value_by_nick(s.to_str().ok()?)
+        }
+        (String(s), t) if t.is_a(Type::FLAGS) => {
+            let flags_class = glib::FlagsClass::new(t)?;
+            let mut builder = flags_class.builder();
+            for nick in s.to_str().ok()?.split('|').map(str::trim).filter(|n| !n.is_empty()) {
+                builder = builder.set_by_nick(nick);
+            }
+            builder.build()
+        }
+        (String(s), t) if t == gdk::RGBA::static_type() => {
+            Some(gdk::RGBA::parse(s.to_str().ok()?).ok()?.to_value())
+        }
+        (Table(table), t) if t == <Vec<String>>::static_type() => {
+            let strings = table
+                .clone()
+                .sequence_values::<std::string::String>()
+                .collect::<LuaResult<Vec<_>>>()
+                .ok()?;
+            Some(strings.to_value())
+        }
+        (Table(table), t) if t == gtk::Adjustment::static_type() => {
+            let field = |name: &str| table.get::<_, Option<f64>>(name).ok().flatten();
+            let adjustment = gtk::Adjustment::new(
+                field("value").unwrap_or_default(),
+                field("lower").unwrap_or_default(),
+                field("upper").unwrap_or_default(),
+                field("step_increment").unwrap_or_default(),
+                field("page_increment").unwrap_or_default(),
+                field("page_size").unwrap_or_default(),
+            );
+            Some(adjustment.to_value())
+        }
+        (UserData(ud), t) if t.is_a(Type::OBJECT) => {
+            let object = unwrap_object(ud)?;
+            if !object.type_().is_a(t) {
+                println!("Expected an object of type {}, got {}", t, object.type_());
+                return None;
+            }
+            Some(object.to_value())
+        }
+        (v, t) => {
+            println!("Unimplemented Lua->glib conversion: {:?} to {}", v, t);
+            None
+        }
+    }
+}
+
+/// Converts a Lua number into a glib value of the requested numeric type. Enums and flags can be
+/// set by their numeric value too.
+fn number_to_glib(value: &LuaValue, type_: glib::Type) -> Option<glib::Value> {
+    use glib::types::Type;
+    let (integer, number) = match *value {
+        LuaValue::Integer(i) => (Some(i), i as f64),
+        LuaValue::Number(n) if n.fract() == 0.0 => (Some(n as i64), n),
+        LuaValue::Number(n) => (None, n),
+        _ => return None,
+    };
+    match type_ {
+        Type::I8 => Some(i8::try_from(integer?).ok()?.to_value()),
+        Type::U8 => Some(u8::try_from(integer?).ok()?.to_value()),
+        Type::I32 => Some(i32::try_from(integer?).ok()?.to_value()),
+        Type::U32 => Some(u32::try_from(integer?).ok()?.to_value()),
+        Type::I_LONG => Some(glib::ILong(integer?.try_into().ok()?).to_value()),
+        Type::U_LONG => Some(glib::ULong(integer?.try_into().ok()?).to_value()),
+        Type::I64 => Some(integer?.to_value()),
+        Type::U64 => Some(u64::try_from(integer?).ok()?.to_value()),
+        Type::F32 => Some((number as f32).to_value()),
+        Type::F64 => Some(number.to_value()),
+        t if t.is_a(Type::ENUM) => glib::EnumClass::new(t)?.to_value(i32::try_from(integer?).ok()?),
+        t if t.is_a(Type::FLAGS) => {
+            let bits = u32::try_from(integer?).ok()?;
+            let flags_class = glib::FlagsClass::new(t)?;
+            let mut builder = flags_class.builder();
+            let mut remaining = bits;
+            for flags_value in flags_class.values() {
+                let v = flags_value.value();
+                if v != 0 && bits & v == v {
+                    builder = builder.set(v);
+                    remaining &= !v;
+                }
+            }
+            // Reject bits that don't correspond to any known flag.
+            if remaining != 0 {
+                return None;
+            }
+            builder.build()
+        }
         t => {
-            println!("Unimplemented Lua->glib conversion: {:?}", t);
+            println!("Unimplemented Lua->glib conversion: {:?} to {}", value, t);
             None
         }
     }
 }
 
-struct Widget {
-    lua: &'static Lua,
-    widget: gtk::Widget,
-    signal_ids: Vec<(gtk::Widget, SignalHandlerId)>,
+/// Returns the value a signal handler should return when the callback didn't provide one.
+fn default_value(type_: glib::Type) -> Option<glib::Value> {
+    if type_ == glib::Type::UNIT {
+        None
+    } else {
+        Some(glib::Value::from_type(type_))
+    }
 }
 
-impl Widget {
-    fn new(lua: &'static Lua, widget: gtk::Widget) -> Self {
-        Self {
-            lua,
-            widget,
-            signal_ids: Vec::new(),
-        }
+/// Wraps an object as userdata, exposing widget methods if it's a widget.
+fn wrap_object(lua: &Lua, object: glib::Object) -> LuaResult<LuaValue> {
+    match object.downcast::<gtk::Widget>() {
+        Ok(widget) => Widget::new(widget).to_lua(lua),
+        Err(object) => Object::new(object).to_lua(lua),
     }
 }
 
-impl LuaUserData for Widget {
-    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method_mut(
-            super::CONNECT,
-            |_, this, (signal, after, callback): (String, bool, LuaFunction)| {
-                let lua: &'static Lua = this.lua;
-
-                let callback_key = lua
-                    .create_registry_value(callback)
-                    .expect("Failed to create Lua registry value");
-                let signal_id = this.widget.connect_local(&signal, after, move |values| {
-                    /*
-                    let lua_values = match values.iter().map(|v| glib_to_lua(lua, v)).collect::<Option<Vec<LuaValue>>>() {
-                        Some(lua_values) => lua_values,
-                        None => {
-                            println!("Failed to convert one or more glib values to Lua");
-                            return None;
-                        },
-                    };
-                    */
-
-                    // NOTE: This is very hacky, but when passing the widget reference into the
-                    // callback arguments directly, it doesn't seem to have any methods registered.
-                    // TODO: try to cast values[0] to a widget
-                    if values.len() > 0 {
-                        if let Ok(widget_value) =
-                            values[0].transform_with_type(gtk::Widget::static_type())
-                        {
-                            if let Err(err) = lua
-                                .globals()
-                                .set("this", Widget::new(lua, widget_value.get().unwrap()))
-                            {
-                                println!("Failed to set 'this' value before callback: {}", err);
-                            }
-                        }
-                    }
+/// Returns the object wrapped by userdata, if it wraps one.
+fn unwrap_object(ud: &LuaAnyUserData) -> Option<glib::Object> {
+    if let Ok(widget) = ud.borrow::<Widget>() {
+        return Some(widget.object().clone());
+    }
+    if let Ok(object) = ud.borrow::<Object>() {
+        return Some(object.object().clone());
+    }
+    None
+}
 
-                    let f: LuaFunction = lua.registry_value(&callback_key).unwrap();
-                    let retvals = match f.call::<_, LuaMultiValue>(/*lua_values*/ ()) {
-                        Ok(retval) => retval,
-                        Err(err) => {
-                            println!("Error calling Lua callback: {:?}", err);
-                            return None;
-                        }
-                    }
-                    .into_vec();
-
-                    match retvals.len() {
-                        0 => None,
-                        1 => lua_to_glib(&retvals[0]),
-                        n => {
-                            println!("Cannot return {} values in callback", n);
-                            None
-                        }
+/// Implemented by userdata types that wrap a GObject.
+trait ObjectWrapper {
+    fn object(&self) -> &glib::Object;
+}
+
+/// Adds the methods that are available on every wrapped object.
+fn add_object_methods<'lua, T, M>(methods: &mut M)
+where
+    T: LuaUserData + ObjectWrapper,
+    M: LuaUserDataMethods<'lua, T>,
+{
+    methods.add_method(
+        super::CONNECT,
+        |lua, this, (signal, after, callback): (String, bool, LuaFunction)| {
+            // Signals with a return value, like close-request, need the callback's return value
+            // converted to the declared type.
+            let return_type =
+                match glib::subclass::SignalId::parse_name(&signal, this.object().type_(), false) {
+                    Some((signal_id, _)) => signal_id.query().return_type().type_(),
+                    None => {
+                        println!("Signal '{}' not found", &signal);
+                        return Err(LuaError::ExternalError(Arc::new(
+                            crate::error::Error::SignalNotFound(signal),
+                        )));
                     }
-                });
+                };
 
-                this.signal_ids.push((this.widget.clone(), signal_id));
-                Ok(())
-            },
-        );
+            let callback_key = lua
+                .create_registry_value(callback)
+                .expect("Failed to create Lua registry value");
+            let lua_handle = weak_handle(lua);
+            let signal_name = signal.clone();
+            let signal_id = this.object().connect_local(&signal, after, move |values| {
+                let lua = match lua_handle.upgrade() {
+                    Some(lua) => lua,
+                    None => return default_value(return_type),
+                };
 
-        methods.add_method(
-            super::GET_PROPERTY,
-            |_, this, property_name: String| match this.widget.find_property(&property_name) {
-                Some(prop) => Ok(glib_to_lua(
-                    this.lua,
-                    this.widget.property_value(prop.name()),
-                )),
-                None => {
-                    println!("Property '{}' not found", &property_name);
-                    Err(LuaError::ExternalError(Arc::new(
-                        crate::error::Error::PropertyNotFound(property_name),
-                    )))
+                // The first value is the object that emitted the signal, followed by the signal's
+                // own arguments.
+                let args = values
+                    .iter()
+                    .map(|value| glib_to_lua(&lua, value).unwrap_or(LuaValue::Nil))
+                    .collect();
+
+                let f: LuaFunction = lua.registry_value(&callback_key).unwrap();
+                let retvals = match f.call::<_, LuaMultiValue>(LuaMultiValue::from_vec(args)) {
+                    Ok(retval) => retval,
+                    Err(err) => {
+                        println!("Error calling Lua callback for '{}': {:?}", &signal_name, err);
+                        return default_value(return_type);
+                    }
                 }
-            },
-        );
+                .into_vec();
 
-        methods.add_method(
-            super::SET_PROPERTY,
-            |_, this, (property_name, property_value): (String, LuaValue)| {
-                let value = match lua_to_glib(&property_value) {
-                    Some(value) => value,
-                    None => {
+                if return_type == glib::Type::UNIT {
+                    return None;
+                }
+                match retvals.len() {
+                    0 => default_value(return_type),
+                    1 => lua_to_glib(&retvals[0], return_type).or_else(|| {
                         println!(
-                            "Failed to convert property value to glib: {:?}",
-                            &property_value
+                            "Callback for '{}' should return a value of type {}",
+                            &signal_name, return_type
                         );
-                        return Err(LuaError::ExternalError(Arc::new(
-                            crate::error::Error::NoConversionError,
-                        )));
+                        default_value(return_type)
+                    }),
+                    n => {
+                        println!("Cannot return {} values in callback", n);
+                        default_value(return_type)
                     }
-                };
-
-                if let Err(err) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                    this.widget.set_property_from_value(&property_name, &value);
-                })) {
-                    let s = format!("Failed to set property '{}': {:?}", &property_name, err);
-                    println!("{}", &s);
-                    return Err(LuaError::ExternalError(Arc::new(crate::error::Error::Any(
-                        s,
-                    ))));
                 }
+            });
 
-                Ok(())
-            },
-        );
+            if let Some(mut handlers) = lua.app_data_mut::<SignalHandlers>() {
+                handlers.0.push((this.object().downgrade(), signal_id));
+            }
+            Ok(())
+        },
+    );
+
+    methods.add_method(
+        super::GET_PROPERTY,
+        |lua, this, property_name: String| match this.object().find_property(&property_name) {
+            Some(prop) => Ok(glib_to_lua(
+                lua,
+                &this.object().property_value(prop.name()),
+            )),
+            None => {
+                println!("Property '{}' not found", &property_name);
+                Err(LuaError::ExternalError(Arc::new(
+                    crate::error::Error::PropertyNotFound(property_name),
+                )))
+            }
+        },
+    );
+
+    methods.add_method(
+        super::SET_PROPERTY,
+        |_, this, (property_name, property_value): (String, LuaValue)| {
+            set_property(this.object(), &property_name, &property_value)
+        },
+    );
+}
+
+/// Sets a property on an object, converting the Lua value to the property's type.
+fn set_property(
+    object: &glib::Object,
+    property_name: &str,
+    property_value: &LuaValue,
+) -> LuaResult<()> {
+    let prop = match object.find_property(property_name) {
+        Some(prop) => prop,
+        None => {
+            println!("Property '{}' not found", property_name);
+            return Err(LuaError::ExternalError(Arc::new(
+                crate::error::Error::PropertyNotFound(property_name.to_string()),
+            )));
+        }
+    };
+
+    let value = match lua_to_glib(property_value, prop.value_type()) {
+        Some(value) => value,
+        None => {
+            println!(
+                "Failed to convert property value to glib: {:?}",
+                property_value
+            );
+            return Err(LuaError::ExternalError(Arc::new(
+                crate::error::Error::NoConversionError,
+            )));
+        }
+    };
+
+    if let Err(err) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        object.set_property_from_value(property_name, &value);
+    })) {
+        let s = format!("Failed to set property '{}': {:?}", property_name, err);
+        println!("{}", &s);
+        return Err(LuaError::ExternalError(Arc::new(crate::error::Error::Any(
+            s,
+        ))));
+    }
+
+    Ok(())
+}
+
+struct Widget {
+    widget: gtk::Widget,
+}
+
+impl Widget {
+    fn new(widget: gtk::Widget) -> Self {
+        Self { widget }
+    }
+}
+
+impl ObjectWrapper for Widget {
+    fn object(&self) -> &glib::Object {
+        self.widget.upcast_ref()
+    }
+}
+
+impl LuaUserData for Widget {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        add_object_methods(methods);
 
         methods.add_method(super::GET_TEXT, |_, this, ()| {
             // TODO: work for more than Entry widgets?
@@ -410,6 +1081,255 @@ impl LuaUserData for Widget {
             this.widget.set_css_classes(&v);
             Ok(())
         });
+
+        methods.add_method(
+            super::APPEND,
+            |_, this, (child, name): (LuaAnyUserData, Option<String>)| {
+                let child = child.borrow::<Widget>()?;
+                insert_child(&this.widget, &child.widget, None, name.as_deref())
+            },
+        );
+
+        methods.add_method(
+            super::PREPEND,
+            |_, this, (child, name): (LuaAnyUserData, Option<String>)| {
+                let child = child.borrow::<Widget>()?;
+                insert_child(&this.widget, &child.widget, Some(0), name.as_deref())
+            },
+        );
+
+        methods.add_method(
+            super::INSERT,
+            |_, this, (child, position, name): (LuaAnyUserData, i32, Option<String>)| {
+                let child = child.borrow::<Widget>()?;
+                insert_child(&this.widget, &child.widget, Some(position), name.as_deref())
+            },
+        );
+
+        methods.add_method(super::REMOVE, |_, this, child: LuaAnyUserData| {
+            let child = child.borrow::<Widget>()?;
+            remove_child(&this.widget, &child.widget)
+        });
+
+        methods.add_method(
+            super::REORDER,
+            |_, this, (child, position): (LuaAnyUserData, i32)| {
+                let child = child.borrow::<Widget>()?;
+                reorder_child(&this.widget, &child.widget, position)
+            },
+        );
+
+        methods.add_method(
+            super::ATTACH,
+            |_,
+             this,
+             (child, column, row, width, height): (
+                LuaAnyUserData,
+                i32,
+                i32,
+                Option<i32>,
+                Option<i32>,
+            )| {
+                let child = child.borrow::<Widget>()?;
+                if let Some(grid) = this.widget.downcast_ref::<gtk::Grid>() {
+                    grid.attach(
+                        &child.widget,
+                        column,
+                        row,
+                        width.unwrap_or(1),
+                        height.unwrap_or(1),
+                    );
+                    Ok(())
+                } else {
+                    Err(LuaError::ExternalError(Arc::new(
+                        super::Error::UnsupportedOperation,
+                    )))
+                }
+            },
+        );
+
+        methods.add_method(super::CHILDREN, |_, this, ()| {
+            Ok(children(&this.widget)
+                .into_iter()
+                .map(Widget::new)
+                .collect::<Vec<_>>())
+        });
+    }
+}
+
+/// Returns the direct children of a widget, in order. For a Notebook, these are its pages.
+fn children(widget: &gtk::Widget) -> Vec<gtk::Widget> {
+    if let Some(notebook) = widget.downcast_ref::<gtk::Notebook>() {
+        return (0..notebook.n_pages())
+            .filter_map(|page_num| notebook.nth_page(Some(page_num)))
+            .collect();
+    }
+
+    let mut children = Vec::new();
+    let mut child = widget.first_child();
+    while let Some(widget) = child {
+        child = widget.next_sibling();
+        children.push(widget);
+    }
+    children
+}
+
+/// Returns the direct child of `container` that contains `widget`. Containers like ListBox wrap
+/// their children in rows, so the widget that was added isn't always the direct child.
+fn direct_child(container: &gtk::Widget, widget: &gtk::Widget) -> LuaResult<gtk::Widget> {
+    let mut widget = widget.clone();
+    loop {
+        match widget.parent() {
+            Some(parent) if &parent == container => return Ok(widget),
+            Some(parent) => widget = parent,
+            None => {
+                return Err(LuaError::ExternalError(Arc::new(
+                    crate::error::Error::NotAChildError,
+                )))
+            }
+        }
+    }
+}
+
+/// Adds a child to a container at the given position, counting from 0, or at the end if no
+/// position is given. `name` is used as the page name in a Stack and the tab label in a Notebook.
+fn insert_child(
+    container: &gtk::Widget,
+    child: &gtk::Widget,
+    position: Option<i32>,
+    name: Option<&str>,
+) -> LuaResult<()> {
+    if let Some(container) = container.downcast_ref::<gtk::Box>() {
+        match position {
+            Some(position) => {
+                let sibling = match position {
+                    n if n <= 0 => None,
+                    n => {
+                        let siblings = children(container.upcast_ref());
+                        siblings
+                            .get(n as usize - 1)
+                            .or_else(|| siblings.last())
+                            .cloned()
+                    }
+                };
+                container.insert_child_after(child, sibling.as_ref());
+            }
+            None => container.append(child),
+        }
+    } else if let Some(list_box) = container.downcast_ref::<gtk::ListBox>() {
+        list_box.insert(child, position.unwrap_or(-1));
+    } else if let Some(flow_box) = container.downcast_ref::<gtk::FlowBox>() {
+        flow_box.insert(child, position.unwrap_or(-1));
+    } else if let Some(stack) = container.downcast_ref::<gtk::Stack>() {
+        // Stack pages aren't ordered.
+        stack.add_named(child, name);
+    } else if let Some(notebook) = container.downcast_ref::<gtk::Notebook>() {
+        let tab_label = name.map(|name| gtk::Label::new(Some(name)));
+        notebook.insert_page(
+            child,
+            tab_label.as_ref(),
+            position.and_then(|position| u32::try_from(position).ok()),
+        );
+    } else {
+        return Err(LuaError::ExternalError(Arc::new(
+            super::Error::UnsupportedOperation,
+        )));
+    }
+    Ok(())
+}
+
+/// Removes a child from a container.
+fn remove_child(container: &gtk::Widget, child: &gtk::Widget) -> LuaResult<()> {
+    // Notebook pages are held by an internal widget, so they aren't its direct children.
+    if let Some(notebook) = container.downcast_ref::<gtk::Notebook>() {
+        return match notebook.page_num(child) {
+            Some(page_num) => {
+                notebook.remove_page(Some(page_num));
+                Ok(())
+            }
+            None => Err(LuaError::ExternalError(Arc::new(
+                crate::error::Error::NotAChildError,
+            ))),
+        };
+    }
+
+    let child = direct_child(container, child)?;
+    if let Some(container) = container.downcast_ref::<gtk::Box>() {
+        container.remove(&child);
+    } else if let Some(list_box) = container.downcast_ref::<gtk::ListBox>() {
+        list_box.remove(&child);
+    } else if let Some(flow_box) = container.downcast_ref::<gtk::FlowBox>() {
+        flow_box.remove(&child);
+    } else if let Some(grid) = container.downcast_ref::<gtk::Grid>() {
+        grid.remove(&child);
+    } else if let Some(stack) = container.downcast_ref::<gtk::Stack>() {
+        stack.remove(&child);
+    } else {
+        return Err(LuaError::ExternalError(Arc::new(
+            super::Error::UnsupportedOperation,
+        )));
+    }
+    Ok(())
+}
+
+/// Moves a child of a container to the given position, counting from 0.
+fn reorder_child(container: &gtk::Widget, child: &gtk::Widget, position: i32) -> LuaResult<()> {
+    // Notebook pages are held by an internal widget, so they aren't its direct children.
+    if let Some(notebook) = container.downcast_ref::<gtk::Notebook>() {
+        if notebook.page_num(child).is_none() {
+            return Err(LuaError::ExternalError(Arc::new(
+                crate::error::Error::NotAChildError,
+            )));
+        }
+        notebook.reorder_child(child, u32::try_from(position).ok());
+        return Ok(());
+    }
+
+    let child = direct_child(container, child)?;
+    if let Some(container) = container.downcast_ref::<gtk::Box>() {
+        let siblings: Vec<gtk::Widget> = children(container.upcast_ref())
+            .into_iter()
+            .filter(|sibling| sibling != &child)
+            .collect();
+        let sibling = match position {
+            n if n <= 0 => None,
+            n => siblings.get(n as usize - 1).or_else(|| siblings.last()),
+        };
+        container.reorder_child_after(&child, sibling);
+    } else if let Some(list_box) = container.downcast_ref::<gtk::ListBox>() {
+        list_box.remove(&child);
+        list_box.insert(&child, position);
+    } else if let Some(flow_box) = container.downcast_ref::<gtk::FlowBox>() {
+        flow_box.remove(&child);
+        flow_box.insert(&child, position);
+    } else {
+        return Err(LuaError::ExternalError(Arc::new(
+            super::Error::UnsupportedOperation,
+        )));
+    }
+    Ok(())
+}
+
+/// A GObject that isn't a widget, such as an adjustment or a list model.
+struct Object {
+    object: glib::Object,
+}
+
+impl Object {
+    fn new(object: glib::Object) -> Self {
+        Self { object }
+    }
+}
+
+impl ObjectWrapper for Object {
+    fn object(&self) -> &glib::Object {
+        &self.object
+    }
+}
+
+impl LuaUserData for Object {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        add_object_methods(methods);
     }
 }
 
@@ -434,10 +1354,36 @@ impl LuaUserData for Response {
     }
 }
 
+/// A file chosen with `choose_file`, which can be uploaded with `submit_form`. Scripts can see
+/// its name and size, but not where it is.
+struct File {
+    path: PathBuf,
+    name: String,
+    size: Option<u64>,
+}
+
+impl File {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            name: path
+                .file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+            size: std::fs::metadata(&path).ok().map(|metadata| metadata.len()),
+            path,
+        }
+    }
+}
+
+impl LuaUserData for File {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("size", |_, this| Ok(this.size));
+    }
+}
+
 #[allow(dead_code)]
 struct Window {
-    globals: &'static crate::Globals,
-    window: Arc<crate::window::Window>,
+    tab: Arc<crate::tab::Tab>,
 }
 
 impl LuaUserData for Window {
@@ -451,15 +1397,205 @@ mod test {
 
     #[test]
     pub fn test_glib_value_to_lua() {
-        // TODO: finish adding types, and figure out if it's possible to do null
-        let lua = Box::leak(Box::new(Lua::new()));
+        let lua = Lua::new();
         assert_eq!(
-            glib_to_lua(lua, true.to_value()),
+            glib_to_lua(&lua, &true.to_value()),
             Some(LuaValue::Boolean(true))
         );
         assert_eq!(
-            glib_to_lua(lua, false.to_value()),
+            glib_to_lua(&lua, &false.to_value()),
             Some(LuaValue::Boolean(false))
         );
+        assert_eq!(
+            glib_to_lua(&lua, &(-8i8).to_value()),
+            Some(LuaValue::Integer(-8))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &42i32.to_value()),
+            Some(LuaValue::Integer(42))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &42u32.to_value()),
+            Some(LuaValue::Integer(42))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &glib::ILong(-42).to_value()),
+            Some(LuaValue::Integer(-42))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &i64::MIN.to_value()),
+            Some(LuaValue::Integer(i64::MIN))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &u64::MAX.to_value()),
+            Some(LuaValue::Number(u64::MAX as f64))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &1.5f32.to_value()),
+            Some(LuaValue::Number(1.5))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &2.25f64.to_value()),
+            Some(LuaValue::Number(2.25))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &"hello".to_value()),
+            Some(LuaValue::String(lua.create_string("hello").unwrap()))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &None::<String>.to_value()),
+            Some(LuaValue::Nil)
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &gtk::Orientation::Vertical.to_value()),
+            Some(LuaValue::String(lua.create_string("vertical").unwrap()))
+        );
+        assert_eq!(
+            glib_to_lua(
+                &lua,
+                &(glib::BindingFlags::BIDIRECTIONAL | glib::BindingFlags::SYNC_CREATE).to_value()
+            ),
+            Some(LuaValue::String(
+                lua.create_string("bidirectional|sync-create").unwrap()
+            ))
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &None::<glib::Object>.to_value()),
+            Some(LuaValue::Nil)
+        );
+        assert_eq!(
+            glib_to_lua(&lua, &gdk::RGBA::new(1.0, 0.0, 0.0, 1.0).to_value()),
+            Some(LuaValue::String(lua.create_string("rgb(255,0,0)").unwrap()))
+        );
+
+        let strv = glib_to_lua(&lua, &vec![String::from("a"), String::from("b")].to_value());
+        match strv {
+            Some(LuaValue::Table(table)) => assert_eq!(
+                table.sequence_values::<String>().collect::<LuaResult<Vec<_>>>().unwrap(),
+                vec!["a", "b"]
+            ),
+            v => panic!("expected a table, got {:?}", v),
+        }
+    }
+
+    #[test]
+    pub fn test_lua_to_glib() {
+        use glib::types::Type;
+        let lua = Lua::new();
+        let eval = |s: &str| lua.load(s).eval::<LuaValue>().unwrap();
+
+        assert_eq!(
+            lua_to_glib(&eval("true"), Type::BOOL).unwrap().get::<bool>(),
+            Ok(true)
+        );
+        assert_eq!(
+            lua_to_glib(&eval("-3"), Type::I32).unwrap().get::<i32>(),
+            Ok(-3)
+        );
+        assert_eq!(
+            lua_to_glib(&eval("3"), Type::U8).unwrap().get::<u8>(),
+            Ok(3)
+        );
+        assert!(lua_to_glib(&eval("-3"), Type::U32).is_none());
+        assert!(lua_to_glib(&eval("1.5"), Type::I64).is_none());
+        assert_eq!(
+            lua_to_glib(&eval("1.5"), Type::F64).unwrap().get::<f64>(),
+            Ok(1.5)
+        );
+        assert_eq!(
+            lua_to_glib(&eval("2"), Type::F32).unwrap().get::<f32>(),
+            Ok(2.0)
+        );
+        assert_eq!(
+            lua_to_glib(&eval("'label'"), Type::STRING)
+                .unwrap()
+                .get::<String>(),
+            Ok(String::from("label"))
+        );
+        assert_eq!(
+            lua_to_glib(&eval("nil"), Type::STRING)
+                .unwrap()
+                .get::<Option<String>>(),
+            Ok(None)
+        );
+        assert!(lua_to_glib(&eval("nil"), Type::BOOL).is_none());
+
+        let orientation = gtk::Orientation::static_type();
+        assert_eq!(
+            lua_to_glib(&eval("'vertical'"), orientation)
+                .unwrap()
+                .get::<gtk::Orientation>(),
+            Ok(gtk::Orientation::Vertical)
+        );
+        assert_eq!(
+            lua_to_glib(&eval("1"), orientation)
+                .unwrap()
+                .get::<gtk::Orientation>(),
+            Ok(gtk::Orientation::Vertical)
+        );
+        assert!(lua_to_glib(&eval("'diagonal'"), orientation).is_none());
+
+        let binding_flags = glib::BindingFlags::static_type();
+        let expected = glib::BindingFlags::BIDIRECTIONAL | glib::BindingFlags::SYNC_CREATE;
+        assert_eq!(
+            lua_to_glib(&eval("'bidirectional|sync-create'"), binding_flags)
+                .unwrap()
+                .get::<glib::BindingFlags>(),
+            Ok(expected)
+        );
+        assert_eq!(
+            lua_to_glib(&eval("3"), binding_flags)
+                .unwrap()
+                .get::<glib::BindingFlags>(),
+            Ok(expected)
+        );
+
+        assert_eq!(
+            lua_to_glib(&eval("{'a', 'b'}"), <Vec<String>>::static_type())
+                .unwrap()
+                .get::<Vec<String>>(),
+            Ok(vec![String::from("a"), String::from("b")])
+        );
+        assert_eq!(
+            lua_to_glib(&eval("'#00ff00'"), gdk::RGBA::static_type())
+                .unwrap()
+                .get::<gdk::RGBA>(),
+            Ok(gdk::RGBA::new(0.0, 1.0, 0.0, 1.0))
+        );
+
+        assert_eq!(
+            lua_to_glib(&eval("nil"), gtk::Adjustment::static_type())
+                .unwrap()
+                .get::<Option<gtk::Adjustment>>(),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    pub fn test_adjustment_conversion() {
+        // Adjustments can only be created once GTK has been initialized, which needs a display.
+        if gtk::init().is_err() {
+            return;
+        }
+        let lua = Lua::new();
+        let eval = |s: &str| lua.load(s).eval::<LuaValue>().unwrap();
+
+        let adjustment = gtk::Adjustment::new(5.0, 0.0, 10.0, 1.0, 1.0, 0.0);
+        match glib_to_lua(&lua, &adjustment.to_value()) {
+            Some(LuaValue::UserData(ud)) => {
+                assert_eq!(unwrap_object(&ud), Some(adjustment.upcast::<glib::Object>()))
+            }
+            v => panic!("expected userdata, got {:?}", v),
+        }
+
+        let adjustment = lua_to_glib(
+            &eval("{value = 5, lower = 0, upper = 10}"),
+            gtk::Adjustment::static_type(),
+        )
+        .unwrap()
+        .get::<gtk::Adjustment>()
+        .unwrap();
+        assert_eq!(adjustment.value(), 5.0);
+        assert_eq!(adjustment.upper(), 10.0);
     }
 }
@@ -1,5 +1,7 @@
-use crate::window::Window;
+use crate::tab::Tab;
 use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
 
 pub mod lua;
 
@@ -8,10 +10,29 @@ pub const ALERT: &str = "alert";
 pub const FIND_WIDGET: &str = "find_widget";
 pub const SUBMIT_FORM: &str = "submit_form";
 pub const FETCH: &str = "fetch";
+pub const CREATE_WIDGET: &str = "create_widget";
+pub const BUILD_WIDGET: &str = "build_widget";
+pub const CLONE_WIDGET: &str = "clone_widget";
+pub const CHOOSE_FILE: &str = "choose_file";
+
+// privileged global functions, only available to Webby's built-in about: pages
+pub const NAVIGATE: &str = "navigate";
+pub const RELOAD: &str = "reload";
+pub const SEARCH_HISTORY: &str = "search_history";
+pub const CLEAR_HISTORY: &str = "clear_history";
+pub const LIST_BOOKMARKS: &str = "list_bookmarks";
+pub const RENAME_BOOKMARK: &str = "rename_bookmark";
+pub const REMOVE_BOOKMARK: &str = "remove_bookmark";
+pub const CLEAR_COOKIES: &str = "clear_cookies";
+pub const OPEN_COOKIE_MANAGER: &str = "open_cookie_manager";
+pub const VERSION_INFO: &str = "version_info";
 
 // global vars
 pub const WINDOW: &str = "window";
 
+// page hooks
+pub const ON_UNLOAD: &str = "on_unload";
+
 // widget functions
 pub const CONNECT: &str = "connect";
 pub const GET_PROPERTY: &str = "get_property";
@@ -22,6 +43,13 @@ pub const SET_LABEL: &str = "set_label";
 pub const ADD_CSS_CLASS: &str = "add_css_class";
 pub const REMOVE_CSS_CLASS: &str = "remove_css_class";
 pub const SET_CSS_CLASSES: &str = "set_css_classes";
+pub const APPEND: &str = "append";
+pub const PREPEND: &str = "prepend";
+pub const INSERT: &str = "insert";
+pub const REMOVE: &str = "remove";
+pub const REORDER: &str = "reorder";
+pub const ATTACH: &str = "attach";
+pub const CHILDREN: &str = "children";
 
 #[derive(Copy, Clone, Debug)]
 pub enum Lang {
@@ -37,21 +65,60 @@ impl Lang {
     }
 }
 
+/// The scripting environment for a single page. A new one is created every time a page is
+/// rendered so that no state carries over between pages, and dropping it tears down the runtime
+/// along with any callbacks registered by the page's scripts.
+pub struct Environment {
+    lua: Rc<mlua::Lua>,
+}
+
+impl Environment {
+    /// Creates the environment for a page in `tab`. If the page is `privileged`, its scripts can
+    /// also use the functions for Webby's built-in pages.
+    pub fn new(tab: Arc<Tab>, privileged: bool) -> Self {
+        Environment {
+            lua: lua::new(tab, privileged),
+        }
+    }
+
+    /// Runs the page's unload hook and disconnects any signal handlers registered by its scripts.
+    /// The environment should be dropped afterwards.
+    pub fn unload(&self) {
+        lua::unload(&self.lua);
+    }
+}
+
+#[derive(Clone)]
 pub struct Script {
     pub lang: Lang,
     pub text: String,
+    /// URL the text is loaded from, if the script isn't inline.
+    pub src: Option<String>,
 }
 
 impl Script {
     pub fn new(lang: Lang, text: String) -> Script {
-        Script { lang, text }
+        Script {
+            lang,
+            text,
+            src: None,
+        }
+    }
+
+    /// Creates a script whose text still needs to be loaded from `src`.
+    pub fn from_src(lang: Lang, src: String) -> Script {
+        Script {
+            lang,
+            text: String::new(),
+            src: Some(src),
+        }
     }
 
-    pub fn execute(&self, window: &Window) {
+    pub fn execute(&self, env: &Environment) {
         match self.lang {
             Lang::Lua => {
                 println!("Executing Lua: {}", &self.text);
-                if let Err(err) = window.state.lock().unwrap().globals.lua.load(&self.text).exec() {
+                if let Err(err) = env.lua.load(&self.text).exec() {
                     println!("Lua script execution error: {}", err);
                 }
             }
@@ -0,0 +1,1283 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+
+use glib::signal::SignalHandlerId;
+use glib::{clone, Continue, MainContext, PRIORITY_DEFAULT};
+use gtk::prelude::*;
+use gtk::{gdk, gio, glib, pango};
+
+use crate::history::{Entry, WidgetValue};
+use crate::resource::Resource;
+use crate::ui::{Form, Href};
+use crate::visits::SharedVisits;
+use crate::window::Window;
+
+/// A single tab in a window. Each tab loads pages independently, with its own history, styles
+/// and script environment.
+pub struct Tab {
+    window: Weak<Window>,
+    pub visits: SharedVisits,
+    /// The widget shown as the tab's page in the window's notebook.
+    pub widget: gtk::Box,
+    /// The widget shown in the tab's header.
+    pub header: gtk::Box,
+    label: gtk::Label,
+    content: gtk::ScrolledWindow,
+    info_bar: gtk::Revealer,
+    info_bar_text: gtk::Label,
+    status_label: gtk::Label,
+    pub state: Mutex<State>,
+}
+
+pub struct State {
+    pub location: String,
+    /// Text for the address bar, which is the location being loaded if there is one.
+    pub address: String,
+    pub title: Option<String>,
+    pub http_client: reqwest::blocking::Client,
+    pub builder: gtk::Builder,
+    pub ui_definition: Option<crate::ui::Definition>,
+    pub history: crate::history::History,
+    user_styles: Option<gtk::CssProvider>,
+    script_env: Option<crate::script::Environment>,
+    /// Signal handlers connected to the page's widgets for its links and forms.
+    page_handlers: Vec<(gtk::Widget, SignalHandlerId)>,
+    href_controllers: Vec<(gtk::Widget, gtk::EventController)>,
+    scroll_handlers: Vec<(gtk::Adjustment, SignalHandlerId)>,
+    /// Files chosen with the page's file buttons, by the button's object ID.
+    chosen_files: HashMap<String, Vec<PathBuf>>,
+}
+
+/// Messages from the thread submitting a form.
+enum Upload {
+    /// How many bytes of the files being uploaded have been sent, and the total.
+    Progress(u64, u64),
+    Done(crate::Result<Resource>),
+}
+
+impl State {
+    /// Returns the URL that relative links on the current page resolve against.
+    pub fn base_url(&self) -> String {
+        match self.ui_definition.as_ref().and_then(|def| def.base.as_ref()) {
+            Some(base) => crate::util::resolve_url(&self.location, base),
+            None => self.location.clone(),
+        }
+    }
+}
+
+unsafe impl Send for Tab {}
+unsafe impl Sync for Tab {}
+
+impl Tab {
+    pub fn new(
+        window: Weak<Window>,
+        http_client: reqwest::blocking::Client,
+        visits: SharedVisits,
+    ) -> Arc<Self> {
+        let content = gtk::ScrolledWindow::builder()
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+
+        // Errors that don't replace the page are shown below it until they're closed.
+        let info_bar_text = gtk::Label::builder()
+            .hexpand(true)
+            .xalign(0.0)
+            .wrap(true)
+            .build();
+        let info_bar_close_button = gtk::Button::from_icon_name("window-close-symbolic");
+        info_bar_close_button.set_has_frame(false);
+        info_bar_close_button.set_valign(gtk::Align::Start);
+        let info_bar_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        info_bar_box.add_css_class("info-bar");
+        info_bar_box.append(&info_bar_text);
+        info_bar_box.append(&info_bar_close_button);
+        let info_bar = gtk::Revealer::builder()
+            .child(&info_bar_box)
+            .reveal_child(false)
+            .build();
+        info_bar_close_button.connect_clicked(clone!(@weak info_bar => move |_| {
+            info_bar.set_reveal_child(false);
+        }));
+
+        let status_label = gtk::Label::new(None);
+
+        let widget = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        widget.append(&content);
+        widget.append(&info_bar);
+        widget.append(&status_label);
+
+        let label = gtk::Label::builder()
+            .label("New Tab")
+            .ellipsize(pango::EllipsizeMode::End)
+            .max_width_chars(24)
+            .build();
+        let close_button = gtk::Button::from_icon_name("window-close-symbolic");
+        close_button.set_has_frame(false);
+        let header = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        header.append(&label);
+        header.append(&close_button);
+
+        let state = State {
+            location: String::new(),
+            address: String::new(),
+            title: None,
+            http_client,
+            builder: gtk::Builder::new(),
+            ui_definition: None,
+            history: crate::history::History::new(),
+            user_styles: None,
+            script_env: None,
+            page_handlers: Vec::new(),
+            href_controllers: Vec::new(),
+            scroll_handlers: Vec::new(),
+            chosen_files: HashMap::new(),
+        };
+        let tab = Arc::new(Self {
+            window,
+            visits,
+            widget,
+            header,
+            label,
+            content,
+            info_bar,
+            info_bar_text,
+            status_label,
+            state: Mutex::new(state),
+        });
+
+        close_button.connect_clicked(clone!(@weak tab => move |_| {
+            if let Some(window) = tab.window.upgrade() {
+                window.close_tab(&tab);
+            }
+        }));
+
+        tab
+    }
+
+    /// Returns the window that the tab is in, unless it has been closed.
+    pub fn window(&self) -> Option<Arc<Window>> {
+        self.window.upgrade()
+    }
+
+    /// Lets the window know that the tab's location, title or history changed, so that the
+    /// address bar and buttons can be updated if this is the tab being shown.
+    fn update_window(&self) {
+        if let Some(window) = self.window.upgrade() {
+            window.tab_changed(self);
+        }
+    }
+
+    fn set_title(&self, title: Option<String>) {
+        self.label.set_text(title.as_deref().unwrap_or("New Tab"));
+        self.label.set_tooltip_text(title.as_deref());
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(entry) = state.history.current_mut() {
+                entry.title = title.clone();
+            }
+            state.title = title;
+        }
+        self.update_window();
+    }
+
+    /// Remembers the title of the current location in the browsing history.
+    fn set_visit_title(&self, title: String) {
+        let location = self.state.lock().unwrap().location.clone();
+        self.visits.lock().unwrap().set_title(&location, title);
+    }
+
+    /// Remembers how far the current page is scrolled and what has been entered into its input
+    /// widgets, so that they can be restored when the page is returned to.
+    fn save_page_state(&self) {
+        let scroll = (
+            self.content.hadjustment().value(),
+            self.content.vadjustment().value(),
+        );
+        let mut state = self.state.lock().unwrap();
+        let widgets = widget_values(&state.builder);
+        if let Some(entry) = state.history.current_mut() {
+            entry.scroll = scroll;
+            entry.widgets = widgets;
+        }
+    }
+
+    /// Reapplies the state saved in a history entry to the page that was just shown.
+    fn restore_page_state(&self, entry: &Entry) {
+        // The state lock must not be held here, since setting values can trigger script callbacks.
+        let builder = self.state.lock().unwrap().builder.clone();
+        restore_widget_values(&builder, &entry.widgets);
+        self.restore_scroll(entry.scroll);
+    }
+
+    fn restore_scroll(&self, (x, y): (f64, f64)) {
+        let handlers: Vec<(gtk::Adjustment, SignalHandlerId)> = [
+            (self.content.hadjustment(), x),
+            (self.content.vadjustment(), y),
+        ]
+        .into_iter()
+        .filter_map(|(adjustment, value)| {
+            scroll_when_ready(&adjustment, value).map(|handler_id| (adjustment, handler_id))
+        })
+        .collect();
+        self.state.lock().unwrap().scroll_handlers.extend(handlers);
+    }
+
+    pub fn go(self: Arc<Self>, location: String, modify_history: bool) {
+        self.load(location, modify_history, None);
+    }
+
+    /// Loads a page. If it's being returned to through the history, `restore` is the entry it was
+    /// left with.
+    fn load(self: Arc<Self>, location: String, modify_history: bool, restore: Option<Entry>) {
+        let location = crate::util::normalize_location(&location);
+        if modify_history {
+            self.save_page_state();
+        }
+
+        // Like in a web browser, moving to another part of the page being shown doesn't load it
+        // again.
+        let current = {
+            let state = self.state.lock().unwrap();
+            state.ui_definition.as_ref().map(|_| state.location.clone())
+        };
+        match current {
+            Some(current) if crate::util::is_fragment_navigation(&current, &location) => {
+                // Following a link to the part that's already being shown just shows it again.
+                let modify_history = modify_history && location != current;
+                self.go_to_fragment(location, modify_history, restore);
+            }
+            _ => self.load_resource(location, modify_history, restore),
+        }
+    }
+
+    /// Moves to another part of the page being shown, which is the object with the ID in the
+    /// location's fragment.
+    fn go_to_fragment(&self, location: String, modify_history: bool, restore: Option<Entry>) {
+        self.info_bar.set_reveal_child(false);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.location = location.clone();
+            state.address = location.clone();
+            if modify_history {
+                let title = state.title.clone();
+                state.history.push(location.clone(), title);
+            }
+        }
+        match (restore, crate::util::fragment(&location)) {
+            (Some(entry), _) => self.restore_scroll(entry.scroll),
+            (None, Some(id)) => self.show_fragment(&id),
+            (None, None) => self.restore_scroll((0.0, 0.0)),
+        }
+        self.update_window();
+    }
+
+    /// Fetches a page and shows it in place of the current one.
+    fn load_resource(
+        self: Arc<Self>,
+        location: String,
+        modify_history: bool,
+        mut restore: Option<Entry>,
+    ) {
+        self.info_bar.set_reveal_child(false);
+
+        //println!("Navigating to: {}", &location);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.ui_definition = None;
+            state.address = location.clone();
+        }
+        self.update_window();
+
+        // Built-in pages are embedded in Webby itself.
+        if crate::about::is_about(&location) {
+            let result = Resource::from_about(location.clone());
+            self.finish_load(result, location, modify_history, restore);
+            return;
+        }
+
+        // Local files are read directly, which makes it possible to develop pages without
+        // running a server.
+        if let Some(path) = crate::util::file_path(&location) {
+            let result = Resource::from_file(location.clone(), &path);
+            self.finish_load(result, location, modify_history, restore);
+            return;
+        }
+
+        self.status_label
+            .set_label(&format!("Loading {}...", &location));
+        let request = self.state.lock().unwrap().http_client.get(&location);
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        std::thread::spawn(move || {
+            let result = request
+                .send()
+                .map_err(crate::error::Error::from)
+                .and_then(Resource::from_response);
+            if let Err(err) = sender.send(result) {
+                println!("Failed to send response on channel: {}", err);
+            }
+        });
+
+        receiver.attach(None, clone!(@weak self as tab => @default-return Continue(false), move |result| {
+            tab.clone().finish_load(result, location.clone(), modify_history, restore.take());
+            Continue(false)
+        }));
+    }
+
+    fn finish_load(
+        self: Arc<Self>,
+        result: crate::Result<Resource>,
+        location: String,
+        modify_history: bool,
+        restore: Option<Entry>,
+    ) {
+        // The tab moves to the location even if it couldn't be loaded, so that its error page
+        // can be reloaded and gone back from.
+        let url = match &result {
+            Ok(resource) => resource.url.clone(),
+            Err(_) => location.clone(),
+        };
+        self.unload_page();
+        self.content.set_child(gtk::Widget::NONE);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.location = url.clone();
+            state.address = url;
+            // The entry is added before rendering, so that the page's title is stored in it.
+            if modify_history {
+                state.history.push(location, None);
+            }
+        }
+
+        let r#do = || -> crate::Result<()> {
+            let resource = result?;
+            if !crate::about::is_about(&resource.url) {
+                self.visits.lock().unwrap().record(resource.url.clone());
+            }
+            self.clone().render(resource, restore)
+        };
+
+        if let Err(err) = r#do() {
+            self.clone().show_error_page(&err);
+        }
+
+        self.update_window();
+        self.status_label.set_text("");
+    }
+
+    /// Replaces the page with one explaining why it couldn't be shown.
+    fn show_error_page(self: Arc<Self>, err: &crate::error::Error) {
+        println!("Navigation error: {}", err);
+        let location = self.state.lock().unwrap().location.clone();
+        let page = crate::about::error_page(err, &location);
+        // The error page is Webby's own, so it may use privileged functions to reload.
+        if let Err(err) = self.clone().render_gtk(page, None, true) {
+            self.show_error(&err);
+        }
+    }
+
+    pub fn show_error(&self, err: &crate::error::Error) {
+        let err_text = err.to_string().replace(": ", ":\n");
+        self.show_info_bar(&err_text, "error");
+        println!("Navigation error: {}", err);
+    }
+
+    /// Shows the problems that were worked around in a page's UI definition, like unknown web
+    /// attributes, below the page.
+    fn show_diagnostics(&self, diagnostics: &[crate::ui::Diagnostic]) {
+        if diagnostics.is_empty() {
+            return;
+        }
+        let text = diagnostics
+            .iter()
+            .map(|diagnostic| format!("Warning: {}", diagnostic))
+            .collect::<Vec<_>>()
+            .join("\n");
+        println!("{}", text);
+        self.show_info_bar(&text, "warning");
+    }
+
+    /// Reveals the info bar with some text, styled with a CSS class like "error" or "warning".
+    fn show_info_bar(&self, text: &str, css_class: &str) {
+        self.info_bar_text.set_text(text);
+        if let Some(child) = self.info_bar.child() {
+            child.set_css_classes(&["info-bar", css_class]);
+        }
+        self.info_bar.set_reveal_child(true);
+    }
+
+    fn render(self: Arc<Self>, resource: Resource, restore: Option<Entry>) -> crate::Result<()> {
+        // Only Webby's own pages may use privileged script functions.
+        let privileged = crate::about::is_about(&resource.url);
+        let mime_type = resource.content_type;
+        match mime_type.type_() {
+            _ if crate::resource::is_gtk(&mime_type) => {
+                self.render_gtk(resource.body, restore, privileged)
+            }
+            mime::TEXT => self.render_text(resource.body),
+            _ => Err(crate::error::Error::UnsupportedContentTypeError(mime_type.essence_str().to_string())),
+        }
+    }
+
+    /// Submits a form in the background. Once the server responds, `on_complete` is invoked with
+    /// any error that occurred, and then the response is shown. The client follows redirects on its
+    /// own, so the response is for the final page of a Post/Redirect/Get flow, and it's rendered
+    /// as if that page had been loaded, without requesting it again. If there are `files`
+    /// to upload, each paired with the name it's sent with, the form is sent as multipart/form-data
+    /// and the upload's progress is shown in the status bar.
+    pub fn submit_form<F: FnOnce(Option<&crate::error::Error>) + 'static>(
+        self: Arc<Self>,
+        method: reqwest::Method,
+        action: String,
+        values: HashMap<String, String>,
+        files: Vec<(String, PathBuf)>,
+        on_complete: F,
+    ) {
+        let (url, request) = {
+            let state = self.state.lock().unwrap();
+            let url = crate::util::resolve_url(&state.base_url(), &action);
+            let request = state.http_client.request(method.clone(), &url);
+            (url, request)
+        };
+
+        self.status_label
+            .set_label(&format!("Submitting form to {}...", &url));
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        std::thread::spawn(move || {
+            let request = if !files.is_empty() {
+                let progress = sender.clone();
+                let form = crate::resource::multipart_form(values, files, move |sent, total| {
+                    // The receiver is gone if the tab was closed, in which case nobody cares.
+                    let _ = progress.send(Upload::Progress(sent, total));
+                });
+                match form {
+                    Ok(form) => request.multipart(form),
+                    Err(err) => {
+                        let _ = sender.send(Upload::Done(Err(err)));
+                        return;
+                    }
+                }
+            } else if method == reqwest::Method::GET {
+                // Like in HTML, GET forms send their values in the query string.
+                request.query(&values)
+            } else {
+                request.form(&values)
+            };
+
+            // The client follows redirects on its own, so the response is for the final page.
+            let result = request
+                .send()
+                .map_err(crate::error::Error::from)
+                .and_then(Resource::from_response);
+            if let Err(err) = sender.send(Upload::Done(result)) {
+                println!("Failed to send form response on channel: {}", err);
+            }
+        });
+
+        let mut on_complete = Some(on_complete);
+        receiver.attach(None, clone!(@weak self as tab => @default-return Continue(false), move |upload| {
+            let result = match upload {
+                Upload::Progress(sent, total) => {
+                    tab.status_label.set_label(&format!(
+                        "Uploading to {}... {}% ({} of {})",
+                        &url,
+                        sent * 100 / total,
+                        glib::format_size(sent),
+                        glib::format_size(total),
+                    ));
+                    return Continue(true);
+                }
+                Upload::Done(result) => result,
+            };
+            if let Some(on_complete) = on_complete.take() {
+                on_complete(result.as_ref().err());
+            }
+            match result {
+                Ok(resource) => {
+                    let url = resource.url.clone();
+                    tab.info_bar.set_reveal_child(false);
+                    tab.clone().finish_load(Ok(resource), url, true, None);
+                }
+                Err(err) => {
+                    tab.show_error(&err);
+                    tab.status_label.set_text("");
+                }
+            }
+            Continue(false)
+        }));
+    }
+
+    /// Opens a file chooser for picking any number of files, and calls `on_chosen` with their
+    /// paths once it's closed. Nothing is chosen if it's cancelled, and files that aren't on the
+    /// local filesystem are left out.
+    pub fn choose_files<F: FnOnce(Vec<PathBuf>) + 'static>(&self, on_chosen: F) {
+        let dialog = gtk::FileDialog::builder()
+            .title("Choose Files")
+            .modal(true)
+            .build();
+        let window = self.window();
+        dialog.open_multiple(
+            window.as_ref().map(|window| &window.app_window),
+            gio::Cancellable::NONE,
+            move |result| {
+                let files = match result {
+                    Ok(files) => files,
+                    Err(err) => {
+                        // Cancelling the dialog is reported as an error too.
+                        if !err.matches(gtk::DialogError::Dismissed) {
+                            println!("Failed to choose files: {}", err);
+                        }
+                        on_chosen(Vec::new());
+                        return;
+                    }
+                };
+                let paths = (0..files.n_items())
+                    .filter_map(|i| files.item(i))
+                    .filter_map(|file| file.downcast::<gio::File>().ok())
+                    .filter_map(|file| file.path())
+                    .collect();
+                on_chosen(paths);
+            },
+        );
+    }
+
+    fn render_text(self: Arc<Self>, s: String) -> crate::Result<()> {
+        self.content.set_child(Some(&gtk::TextView::with_buffer(
+            &gtk::TextBuffer::builder().text(&s).build(),
+        )));
+        let location = {
+            let mut state = self.state.lock().unwrap();
+            state.builder = gtk::Builder::new();
+            state.location.clone()
+        };
+        self.set_title(Some(location));
+        Ok(())
+    }
+
+    /// Renders a UI definition. `restore` is the history entry to restore the page from, if it's
+    /// being returned to, and `privileged` is whether its scripts may use privileged functions.
+    pub fn render_gtk(
+        self: Arc<Self>,
+        s: String,
+        mut restore: Option<Entry>,
+        privileged: bool,
+    ) -> crate::Result<()> {
+        let mut ui_definition = crate::ui::Definition::new(s)?;
+        if !ui_definition.has_remote_sources() {
+            return self.show_page(ui_definition, restore, privileged);
+        }
+
+        // Scripts and styles with a src attribute are loaded before the page is shown, with the
+        // tab's client so that cookies are sent along.
+        let (location, http_client) = {
+            let state = self.state.lock().unwrap();
+            (state.location.clone(), state.http_client.clone())
+        };
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        let base = match &ui_definition.base {
+            Some(base) => crate::util::resolve_url(&location, base),
+            None => location.clone(),
+        };
+        // Whether local files may be loaded depends on where the page itself came from, not on
+        // its base.
+        let page_location = location.clone();
+        std::thread::spawn(move || {
+            ui_definition.load_sources(|src| {
+                let url = crate::util::resolve_url(&base, src);
+                crate::resource::fetch_text(&http_client, &page_location, &url)
+            });
+            if let Err(err) = sender.send(ui_definition) {
+                println!("Failed to send UI definition on channel: {}", err);
+            }
+        });
+
+        receiver.attach(None, clone!(@weak self as tab => @default-return Continue(false), move |ui_definition| {
+            // Don't show the page if another one has been loaded in the meantime.
+            if tab.state.lock().unwrap().location != location {
+                return Continue(false);
+            }
+            if let Err(err) = tab.clone().show_page(ui_definition, restore.take(), privileged) {
+                tab.clone().show_error_page(&err);
+            }
+            Continue(false)
+        }));
+        Ok(())
+    }
+
+    /// Shows a page once all of its scripts and styles have been loaded.
+    fn show_page(
+        self: Arc<Self>,
+        ui_definition: crate::ui::Definition,
+        restore: Option<Entry>,
+        privileged: bool,
+    ) -> crate::Result<()> {
+        // Construct the GTK builder from the UI definition.
+        let builder = gtk::Builder::new();
+        builder
+            .add_from_string(&ui_definition.buildable)
+            .map_err(|err| ui_definition.locate_builder_error(err))?;
+
+        // Release the page being shown, if there still is one. Loading a location has already
+        // released it, but rendering from the editor hasn't, so that an invalid page leaves it be.
+        self.unload_page();
+        self.show_diagnostics(&ui_definition.diagnostics);
+
+        // If the new page has styles, apply them.
+        let stylesheet = ui_definition.stylesheet();
+        if !stylesheet.is_empty() {
+            let user_styles = gtk::CssProvider::new();
+            user_styles.load_from_data(&stylesheet);
+            gtk::style_context_add_provider_for_display(
+                &self.display(),
+                &user_styles,
+                gtk::STYLE_PROVIDER_PRIORITY_USER,
+            );
+            self.state.lock().unwrap().user_styles = Some(user_styles);
+        }
+
+        // Set the title to that requested by the user, or the location if there was none.
+        let title = ui_definition
+            .title
+            .clone()
+            .unwrap_or(self.state.lock().unwrap().location.clone());
+        self.set_title(Some(title.clone()));
+        if !privileged {
+            self.set_visit_title(title);
+        }
+
+        // Find the "body" widget, and set it as the tab's content.
+        match builder.object::<gtk::Widget>("body") {
+            Some(body) /* once told me */ => self.content.set_child(Some(&body)),
+            None => println!("No object found named 'body'"),
+        }
+
+        // Set up callbacks for any href attributes and links in labels.
+        self.clone().connect_links(&builder, &ui_definition.hrefs);
+        self.clone().connect_forms(&builder, &ui_definition.forms);
+
+        // Scripts can clone widgets from the definition, so it needs to be in place first.
+        let scripts = ui_definition.scripts.clone();
+        {
+            let mut state = self.state.lock().unwrap();
+            state.builder = builder;
+            state.ui_definition = Some(ui_definition);
+        }
+
+        // Run any defined scripts in a fresh environment, so that nothing from the previous page
+        // carries over. The state lock must not be held here, since scripts may need it.
+        let script_env = crate::script::Environment::new(self.clone(), privileged);
+        for script in &scripts {
+            script.execute(&script_env);
+        }
+        self.state.lock().unwrap().script_env = Some(script_env);
+
+        // Restore the page as it was left, after the scripts have had a chance to set it up, or
+        // show the part of it that was linked to.
+        let fragment = crate::util::fragment(&self.state.lock().unwrap().location);
+        match (restore, fragment) {
+            (Some(entry), _) => self.restore_page_state(&entry),
+            (None, Some(id)) => self.show_fragment(&id),
+            (None, None) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Connects the hrefs of the objects in a builder, and makes links in the markup of its labels
+    /// navigate within Webby rather than being opened by the desktop.
+    pub fn connect_links(self: Arc<Self>, builder: &gtk::Builder, hrefs: &HashMap<String, Href>) {
+        for (object_id, href) in hrefs {
+            match builder.object::<gtk::Widget>(object_id) {
+                Some(widget) => self.clone().connect_href(widget, href.clone()),
+                None => println!(
+                    "href: no object with id, or object is of the wrong type: {}",
+                    object_id
+                ),
+            }
+        }
+
+        for object in builder.objects() {
+            let label = match object.downcast::<gtk::Label>() {
+                Ok(label) => label,
+                Err(_) => continue,
+            };
+            // Labels with an href of their own already handle their links.
+            if label.buildable_id().map_or(false, |id| hrefs.contains_key(id.as_str())) {
+                continue;
+            }
+            let tab = self.clone();
+            let handler_id = label.connect_activate_link(move |_, uri| {
+                tab.clone().follow_link(uri);
+                gtk::Inhibit(true)
+            });
+            self.state
+                .lock()
+                .unwrap()
+                .page_handlers
+                .push((label.upcast(), handler_id));
+        }
+    }
+
+    /// Makes the submit buttons of forms submit them, as does pressing Enter in one of their
+    /// entries, and makes their file buttons open a file chooser.
+    fn connect_forms(self: Arc<Self>, builder: &gtk::Builder, forms: &[Form]) {
+        for form in forms {
+            for (_, id) in &form.files {
+                match builder.object::<gtk::Widget>(id) {
+                    Some(widget) if has_signal(&widget, "clicked") => {
+                        let tab = self.clone();
+                        let id = id.clone();
+                        let handler_id = widget.connect_local("clicked", false, move |values| {
+                            let button = values[0].get::<gtk::Widget>().ok()?;
+                            tab.clone().choose_files_for(id.clone(), button);
+                            None
+                        });
+                        self.state
+                            .lock()
+                            .unwrap()
+                            .page_handlers
+                            .push((widget, handler_id));
+                    }
+                    _ => println!("form: no file button with id: {}", id),
+                }
+            }
+
+            let mut triggers = Vec::new();
+            for (_, id) in &form.inputs {
+                if let Some(widget) = builder.object::<gtk::Widget>(id) {
+                    if widget.is::<gtk::Entry>() || widget.is::<gtk::PasswordEntry>() {
+                        triggers.push((widget, "activate"));
+                    }
+                }
+            }
+            for id in &form.submits {
+                match builder.object::<gtk::Widget>(id) {
+                    Some(widget) if has_signal(&widget, "clicked") => {
+                        triggers.push((widget, "clicked"))
+                    }
+                    Some(widget) if has_signal(&widget, "activate") => {
+                        triggers.push((widget, "activate"))
+                    }
+                    _ => println!("form: no button with id: {}", id),
+                }
+            }
+
+            for (widget, signal) in triggers {
+                let tab = self.clone();
+                let form = form.clone();
+                let handler_id = widget.connect_local(signal, false, move |_| {
+                    tab.clone().submit(&form);
+                    None
+                });
+                self.state
+                    .lock()
+                    .unwrap()
+                    .page_handlers
+                    .push((widget, handler_id));
+            }
+        }
+    }
+
+    /// Lets the user choose the files for a file button, which are remembered until the page is
+    /// unloaded. Cancelling keeps the files that were already chosen.
+    fn choose_files_for(self: Arc<Self>, id: String, button: gtk::Widget) {
+        let tab = self.clone();
+        let builder = self.state.lock().unwrap().builder.clone();
+        self.choose_files(move |paths| {
+            // Another page may have been loaded while the chooser was open.
+            if paths.is_empty() || tab.state.lock().unwrap().builder != builder {
+                return;
+            }
+            let names = paths
+                .iter()
+                .filter_map(|path| path.file_name())
+                .map(|name| name.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ");
+            button.set_tooltip_text(Some(&names));
+            tab.status_label.set_text(&format!("Chose {}", names));
+            tab.state.lock().unwrap().chosen_files.insert(id, paths);
+        });
+    }
+
+    /// Submits a form with the current values of its inputs and the files chosen for it.
+    fn submit(self: Arc<Self>, form: &Form) {
+        let builder = self.state.lock().unwrap().builder.clone();
+        let mut values = HashMap::new();
+        for (name, id) in &form.inputs {
+            match builder.object::<glib::Object>(id) {
+                Some(object) => {
+                    if let Some(value) = form_value(&object) {
+                        values.insert(name.clone(), value);
+                    }
+                }
+                None => println!("form: no input with id: {}", id),
+            }
+        }
+        let files = {
+            let state = self.state.lock().unwrap();
+            let mut files = Vec::new();
+            for (name, id) in &form.files {
+                for path in state.chosen_files.get(id).into_iter().flatten() {
+                    files.push((name.clone(), path.clone()));
+                }
+            }
+            files
+        };
+        let method = match form.method.as_str() {
+            "POST" => reqwest::Method::POST,
+            _ => reqwest::Method::GET,
+        };
+        self.submit_form(method, form.action.clone(), values, files, |_| ());
+    }
+
+    /// Follows a link in a label's markup. Web pages, files and Webby's own pages are loaded in
+    /// the tab, and anything else, like a mailto: link, is opened by the desktop.
+    fn follow_link(self: Arc<Self>, uri: &str) {
+        let location = self.resolve(uri);
+        let scheme = url::Url::parse(&location)
+            .map_or(String::new(), |url| url.scheme().to_string());
+        match scheme.as_str() {
+            "http" | "https" | "file" | "about" => self.go(location, true),
+            _ => {
+                let window = self.window();
+                gtk::UriLauncher::new(&location).launch(
+                    window.as_ref().map(|window| &window.app_window),
+                    gio::Cancellable::NONE,
+                    move |result| {
+                        if let Err(err) = result {
+                            println!("Failed to open {}: {}", location, err);
+                        }
+                    },
+                );
+            }
+        }
+    }
+
+    /// Shows the object with an ID: any Stack or Notebook pages that it's in are switched to, and
+    /// then it's scrolled to and focused.
+    fn show_fragment(&self, id: &str) {
+        let builder = self.state.lock().unwrap().builder.clone();
+        let widget = match builder.object::<gtk::Widget>(id) {
+            Some(widget) => widget,
+            None => {
+                println!("No object found with id: {}", id);
+                return;
+            }
+        };
+
+        let mut child = widget.clone();
+        while let Some(parent) = child.parent() {
+            if let Some(stack) = parent.downcast_ref::<gtk::Stack>() {
+                stack.set_visible_child(&child);
+            } else if let Some(notebook) = parent.downcast_ref::<gtk::Notebook>() {
+                // Notebook pages are inside a widget of its own, so they aren't its children.
+                let page = (0..notebook.n_pages()).find(|&i| {
+                    notebook
+                        .nth_page(Some(i))
+                        .map_or(false, |page| page == widget || widget.is_ancestor(&page))
+                });
+                if page.is_some() {
+                    notebook.set_current_page(page);
+                }
+            }
+            child = parent;
+        }
+        widget.grab_focus();
+
+        // The widget's position isn't known until the page has been laid out, which may not have
+        // happened yet if it was just shown or switched to.
+        let content = self.content.clone();
+        let frames = Cell::new(0);
+        widget.add_tick_callback(move |widget, _| {
+            frames.set(frames.get() + 1);
+            if frames.get() < 2 {
+                return Continue(true);
+            }
+            let body = match content.child() {
+                Some(child) => match child.downcast::<gtk::Viewport>() {
+                    Ok(viewport) => viewport.child(),
+                    Err(child) => Some(child),
+                },
+                None => None,
+            };
+            let position = body.and_then(|body| widget.translate_coordinates(&body, 0.0, 0.0));
+            if let Some((x, y)) = position {
+                content.hadjustment().set_value(x);
+                content.vadjustment().set_value(y);
+            }
+            Continue(false)
+        });
+    }
+
+    /// Makes a widget navigate to its href's target when it's activated, or open it in a new tab
+    /// when it's middle-clicked, and shows the target in the status bar while it's hovered. The
+    /// handlers are disconnected when the page is unloaded.
+    fn connect_href(self: Arc<Self>, widget: gtk::Widget, href: Href) {
+        let target = href.target;
+        match href.signal {
+            Some(signal) => self.clone().connect_href_signal(&widget, &signal, target.clone()),
+            None => self.clone().connect_href_activation(&widget, target.clone()),
+        }
+
+        let gesture = gtk::GestureClick::new();
+        gesture.set_button(gdk::BUTTON_MIDDLE);
+        let tab = self.clone();
+        let middle_target = target.clone();
+        gesture.connect_released(move |gesture, _, _, _| {
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+            tab.open_in_new_tab(&middle_target);
+        });
+        self.add_href_controller(&widget, gesture);
+
+        let motion = gtk::EventControllerMotion::new();
+        let tab = self.clone();
+        let hover_target = target.clone();
+        motion.connect_enter(move |_, _, _| {
+            tab.status_label.set_text(&tab.resolve(&hover_target));
+        });
+        let tab = self.clone();
+        motion.connect_leave(move |_| {
+            // Leave the status alone if it has changed since, like when a page started loading.
+            if tab.status_label.text() == tab.resolve(&target) {
+                tab.status_label.set_text("");
+            }
+        });
+        self.add_href_controller(&widget, motion);
+    }
+
+    /// Navigates to `target` when a widget is activated, using the signal that fits the type of
+    /// widget.
+    fn connect_href_activation(self: Arc<Self>, widget: &gtk::Widget, target: String) {
+        let has_markup = widget
+            .downcast_ref::<gtk::Label>()
+            .map_or(false, |label| label.uses_markup());
+        if widget.is::<gtk::LinkButton>() || has_markup {
+            // Otherwise the link would be opened by the default handler.
+            self.connect_href_signal(widget, "activate-link", target);
+        } else if widget.is::<gtk::ListBoxRow>() || widget.is::<gtk::FlowBoxChild>() {
+            self.connect_child_activation(widget, target);
+        } else if has_signal(widget, "clicked") {
+            self.connect_href_signal(widget, "clicked", target);
+        } else if has_signal(widget, "activate") {
+            self.connect_href_signal(widget, "activate", target);
+        } else {
+            let gesture = gtk::GestureClick::new();
+            let tab = self.clone();
+            gesture.connect_released(move |gesture, _, _, _| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                tab.clone().href(&target);
+            });
+            self.add_href_controller(widget, gesture);
+        }
+    }
+
+    /// Navigates to `target` when a widget emits a signal. Signals like activate-link return
+    /// whether they were handled, in which case the default handler is stopped.
+    fn connect_href_signal(self: Arc<Self>, widget: &gtk::Widget, signal: &str, target: String) {
+        let signal_id = glib::subclass::SignalId::parse_name(signal, widget.type_(), false);
+        let return_type = match signal_id {
+            Some((signal_id, _)) => signal_id.query().return_type().type_(),
+            None => {
+                println!("href: signal '{}' not found on {}", signal, widget.type_());
+                return;
+            }
+        };
+        if return_type != glib::Type::UNIT && return_type != glib::Type::BOOL {
+            println!("href: signal '{}' has an unsupported return type", signal);
+            return;
+        }
+
+        let tab = self.clone();
+        let handler_id = widget.connect_local(signal, false, move |_| {
+            tab.clone().href(&target);
+            (return_type == glib::Type::BOOL).then(|| true.to_value())
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .page_handlers
+            .push((widget.clone(), handler_id));
+    }
+
+    /// Rows of a ListBox and children of a FlowBox are activated through a signal on their
+    /// parent. Ones built by scripts aren't added to their parent until later, so this waits for
+    /// that if needed.
+    fn connect_child_activation(self: Arc<Self>, child: &gtk::Widget, target: String) {
+        if let Some(parent) = child.parent() {
+            self.connect_parent_activation(child, &parent, target);
+            return;
+        }
+        let tab = self.clone();
+        let handler_id = child.connect_notify_local(Some("parent"), move |child, _| {
+            if let Some(parent) = child.parent() {
+                tab.clone()
+                    .connect_parent_activation(child, &parent, target.clone());
+            }
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .page_handlers
+            .push((child.clone(), handler_id));
+    }
+
+    fn connect_parent_activation(
+        self: Arc<Self>,
+        child: &gtk::Widget,
+        parent: &gtk::Widget,
+        target: String,
+    ) {
+        let signal = if parent.is::<gtk::ListBox>() {
+            "row-activated"
+        } else if parent.is::<gtk::FlowBox>() {
+            "child-activated"
+        } else {
+            println!("href: {} is not in a list", child.type_());
+            return;
+        };
+
+        let tab = self.clone();
+        let child = child.downgrade();
+        let handler_id = parent.connect_local(signal, false, move |values| {
+            // The row or child that was activated follows the list itself.
+            let activated = values.get(1).and_then(|value| value.get::<gtk::Widget>().ok());
+            if activated.is_some() && activated == child.upgrade() {
+                tab.clone().href(&target);
+            }
+            None
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .page_handlers
+            .push((parent.clone(), handler_id));
+    }
+
+    fn add_href_controller(
+        &self,
+        widget: &gtk::Widget,
+        controller: impl IsA<gtk::EventController>,
+    ) {
+        widget.add_controller(controller.clone());
+        self.state
+            .lock()
+            .unwrap()
+            .href_controllers
+            .push((widget.clone(), controller.upcast()));
+    }
+
+    /// Releases everything held by the current page: its script environment, the signal handlers
+    /// connected to its widgets, its styles and the files chosen for its forms.
+    pub fn unload_page(&self) {
+        let (script_env, page_handlers, href_controllers, scroll_handlers, user_styles) = {
+            let mut state = self.state.lock().unwrap();
+            state.chosen_files.clear();
+            (
+                state.script_env.take(),
+                std::mem::take(&mut state.page_handlers),
+                std::mem::take(&mut state.href_controllers),
+                std::mem::take(&mut state.scroll_handlers),
+                state.user_styles.take(),
+            )
+        };
+
+        // The state lock must not be held here, since the page's on_unload hook may need it.
+        if let Some(script_env) = script_env {
+            script_env.unload();
+        }
+
+        for (widget, handler_id) in page_handlers {
+            widget.disconnect(handler_id);
+        }
+
+        for (widget, controller) in href_controllers {
+            widget.remove_controller(&controller);
+        }
+
+        for (adjustment, handler_id) in scroll_handlers {
+            adjustment.disconnect(handler_id);
+        }
+
+        if let Some(user_styles) = user_styles {
+            gtk::style_context_remove_provider_for_display(&self.display(), &user_styles);
+        }
+    }
+
+    /// Returns the location that a link on the current page leads to.
+    pub fn resolve(&self, target: &str) -> String {
+        crate::util::resolve_url(&self.state.lock().unwrap().base_url(), target)
+    }
+
+    fn href(self: Arc<Self>, target: &String) {
+        let location = self.resolve(target);
+        self.go(location, true);
+    }
+
+    fn open_in_new_tab(&self, target: &String) {
+        let location = self.resolve(target);
+        if let Some(window) = self.window.upgrade() {
+            window.open_tab(Some(location), false);
+        }
+    }
+
+    pub fn back(self: Arc<Self>) {
+        self.save_page_state();
+        let entry = self.state.lock().unwrap().history.back().cloned();
+        if let Some(entry) = entry {
+            self.load(entry.location.clone(), false, Some(entry));
+        }
+    }
+
+    pub fn forward(self: Arc<Self>) {
+        self.save_page_state();
+        let entry = self.state.lock().unwrap().history.forward().cloned();
+        if let Some(entry) = entry {
+            self.load(entry.location.clone(), false, Some(entry));
+        }
+    }
+
+    pub fn reload(self: Arc<Self>) {
+        let location = self.state.lock().unwrap().location.clone();
+        if !location.is_empty() {
+            self.load_resource(location, false, None);
+        }
+    }
+
+    pub fn alert(self: Arc<Self>, text: &str) {
+        let window = self.window();
+        gtk::AlertDialog::builder()
+            .message(text)
+            .modal(true)
+            .build()
+            .show(window.as_ref().map(|window| &window.app_window));
+    }
+
+    pub fn set_status(&self, text: &str) {
+        self.status_label.set_text(text);
+    }
+
+    fn display(&self) -> gdk::Display {
+        WidgetExt::display(&self.widget)
+    }
+}
+
+fn has_signal(widget: &gtk::Widget, signal: &str) -> bool {
+    glib::subclass::SignalId::lookup(signal, widget.type_()).is_some()
+}
+
+/// Returns the values of the input widgets in a page, by builder ID.
+fn widget_values(builder: &gtk::Builder) -> HashMap<String, WidgetValue> {
+    let mut values = HashMap::new();
+    for object in builder.objects() {
+        let id = match object.dynamic_cast_ref::<gtk::Buildable>().and_then(|b| b.buildable_id()) {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        let value = if let Some(spin_button) = object.downcast_ref::<gtk::SpinButton>() {
+            WidgetValue::Number(spin_button.value())
+        } else if let Some(entry) = object.downcast_ref::<gtk::Entry>() {
+            WidgetValue::Text(entry.text().to_string())
+        } else if let Some(switch) = object.downcast_ref::<gtk::Switch>() {
+            WidgetValue::Active(switch.is_active())
+        } else if let Some(check_button) = object.downcast_ref::<gtk::CheckButton>() {
+            WidgetValue::Active(check_button.is_active())
+        } else if let Some(drop_down) = object.downcast_ref::<gtk::DropDown>() {
+            WidgetValue::Selected(drop_down.selected())
+        } else {
+            continue;
+        };
+        values.insert(id, value);
+    }
+    values
+}
+
+/// Returns the value that a form input is submitted with, or None if it isn't sent at all, like
+/// an unchecked CheckButton.
+fn form_value(object: &glib::Object) -> Option<String> {
+    if let Some(spin_button) = object.downcast_ref::<gtk::SpinButton>() {
+        Some(spin_button.value().to_string())
+    } else if let Some(editable) = object.dynamic_cast_ref::<gtk::Editable>() {
+        // Entries and password entries.
+        Some(editable.text().to_string())
+    } else if let Some(switch) = object.downcast_ref::<gtk::Switch>() {
+        switch.is_active().then(|| String::from("on"))
+    } else if let Some(check_button) = object.downcast_ref::<gtk::CheckButton>() {
+        check_button.is_active().then(|| String::from("on"))
+    } else if let Some(drop_down) = object.downcast_ref::<gtk::DropDown>() {
+        // Items of a string list are sent as their string, and anything else by position.
+        match drop_down.selected_item() {
+            Some(item) => Some(match item.downcast::<gtk::StringObject>() {
+                Ok(string) => string.string().to_string(),
+                Err(_) => drop_down.selected().to_string(),
+            }),
+            None => None,
+        }
+    } else if let Some(text_view) = object.downcast_ref::<gtk::TextView>() {
+        let buffer = text_view.buffer();
+        let (start, end) = buffer.bounds();
+        Some(buffer.text(&start, &end, false).to_string())
+    } else if let Some(scale) = object.downcast_ref::<gtk::Scale>() {
+        Some(scale.value().to_string())
+    } else {
+        println!("form: unsupported input type: {}", object.type_());
+        None
+    }
+}
+
+/// Sets input widgets in a page to previously saved values. Widgets that no longer exist, or have
+/// changed type, are skipped.
+fn restore_widget_values(builder: &gtk::Builder, values: &HashMap<String, WidgetValue>) {
+    for (id, value) in values {
+        let object = match builder.object::<glib::Object>(id) {
+            Some(object) => object,
+            None => continue,
+        };
+        match value {
+            WidgetValue::Number(value) => {
+                if let Some(spin_button) = object.downcast_ref::<gtk::SpinButton>() {
+                    spin_button.set_value(*value);
+                }
+            }
+            WidgetValue::Text(text) => {
+                if let Some(entry) = object.downcast_ref::<gtk::Entry>() {
+                    entry.set_text(text);
+                }
+            }
+            WidgetValue::Active(active) => {
+                if let Some(switch) = object.downcast_ref::<gtk::Switch>() {
+                    switch.set_active(*active);
+                } else if let Some(check_button) = object.downcast_ref::<gtk::CheckButton>() {
+                    check_button.set_active(*active);
+                }
+            }
+            WidgetValue::Selected(position) => {
+                if let Some(drop_down) = object.downcast_ref::<gtk::DropDown>() {
+                    drop_down.set_selected(*position);
+                }
+            }
+        }
+    }
+}
+
+/// Scrolls to `value`. A freshly shown page hasn't been laid out yet, so its size may not allow
+/// that right away; in that case, a handler is returned that scrolls once the size is known.
+fn scroll_when_ready(adjustment: &gtk::Adjustment, value: f64) -> Option<SignalHandlerId> {
+    adjustment.set_value(value);
+    if adjustment.value() >= value {
+        return None;
+    }
+
+    // The size is known once the upper bound first changes. Only scroll then, even if the page
+    // turns out to be too short, so that later size changes don't undo the user's own scrolling.
+    let upper = adjustment.upper();
+    let done = Cell::new(false);
+    Some(adjustment.connect_changed(move |adjustment| {
+        if !done.get() && adjustment.upper() != upper {
+            adjustment.set_value(value);
+            done.set(true);
+        }
+    }))
+}
@@ -1,34 +1,102 @@
-use quick_xml::events::{BytesStart, Event};
+use gtk::glib;
+use quick_xml::events::{BytesEnd, BytesStart, Event};
 use quick_xml::name::QName;
 use std::collections::HashMap;
+use std::fmt;
 use std::io::Cursor;
 
 const PREFIX: &[u8] = b"web";
 const SCRIPT_TAG: &[u8] = b"script";
 const STYLE_TAG: &[u8] = b"style";
 const PAGE_TAG: &[u8] = b"page";
+const FORM_TAG: &[u8] = b"form";
 
 pub struct Definition {
     /// The raw UI definition, before processing web-specific extensions.
     pub source: String,
     /// The UI definition with web-specific extensions removed.
     pub buildable: String,
-    /// Map of object id to href target.
-    pub hrefs: HashMap<String, String>,
-    /// List of scripts to execute.
+    /// Map of object id to href.
+    pub hrefs: HashMap<String, Href>,
+    /// Forms, in document order.
+    pub forms: Vec<Form>,
+    /// List of scripts to execute, in document order.
     pub scripts: Vec<crate::script::Script>,
-    // Custom styles
-    pub styles: String,
+    /// Custom styles, in document order.
+    pub styles: Vec<Style>,
     /// Title of the page.
     pub title: Option<String>,
+    /// URL that relative links on the page resolve against instead of the page's own, which may
+    /// itself be relative to the page.
+    pub base: Option<String>,
+    /// Problems that were worked around, like unknown web attributes.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Where each part of the buildable came from, as pairs of byte offsets into the buildable
+    /// and the source, in order.
+    source_map: Vec<(usize, usize)>,
+    /// Byte offset into the source of the first element with each `src` attribute value, for
+    /// reporting ones that fail to load.
+    src_offsets: HashMap<String, usize>,
+}
+
+/// Where an object with a `web:href` attribute links to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Href {
+    pub target: String,
+    /// The signal that navigates to the target, from a `web:href-signal` attribute. If there's
+    /// none, one is chosen based on the type of widget.
+    pub signal: Option<String>,
+}
+
+/// A form from a `<web:form>` element. Clicking one of its submit buttons sends the values of
+/// its inputs to its action.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Form {
+    /// URL to submit to, which is the page itself if it's empty.
+    pub action: String,
+    /// Either `GET` or `POST`.
+    pub method: String,
+    /// The name that each input's value is sent with, from its `web:name` attribute, and the
+    /// input's object ID, in document order.
+    pub inputs: Vec<(String, String)>,
+    /// IDs of the objects with a `web:submit` attribute.
+    pub submits: Vec<String>,
+    /// The name that the files chosen with each file button are sent with, from its `web:file`
+    /// attribute, and the button's object ID, in document order.
+    pub files: Vec<(String, String)>,
+}
+
+/// A stylesheet from a `<web:style>` element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Style {
+    /// URL to load the stylesheet from, if it isn't inline.
+    pub src: Option<String>,
+    pub text: String,
+}
+
+/// A problem in a UI definition that doesn't stop it from being shown, at a 1-based line and
+/// column of its source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
 }
 
 impl Definition {
     pub fn new(source: String) -> super::Result<Definition> {
         let mut hrefs = HashMap::new();
+        let mut forms = Vec::new();
         let mut scripts = Vec::new();
-        let mut styles = String::new();
+        let mut styles = Vec::new();
         let mut title = None;
+        let mut base = None;
 
         let mut reader = quick_xml::Reader::from_str(&source);
         let mut writer = quick_xml::Writer::new(Cursor::new(Vec::new()));
@@ -38,43 +106,62 @@ impl Definition {
             for attr in bs.attributes() {
                 let attr = attr?;
                 attrs.insert(
-                    String::from_utf8(attr.key.0.to_vec()).unwrap(),
-                    String::from_utf8(attr.value.into_owned()).unwrap(),
+                    String::from_utf8(attr.key.0.to_vec())?,
+                    String::from_utf8(attr.value.into_owned())?,
                 );
             }
             Ok(attrs)
         }
 
+        fn script_lang(
+            attrs: &HashMap<String, String>,
+            warnings: &mut Vec<String>,
+        ) -> Option<crate::script::Lang> {
+            match attrs.get("type") {
+                None => {
+                    warnings.push(String::from("script tag found, but no type was specified"));
+                    None
+                }
+                Some(r#type) => {
+                    let lang = crate::script::Lang::from(r#type);
+                    if lang.is_none() {
+                        warnings.push(format!("script tag found with unknown type '{}'", r#type));
+                    }
+                    lang
+                }
+            }
+        }
+
         let mut id_autogenerator = IdAutogenerator::new();
 
-        let mut trim_bytes_start = |bs: &BytesStart| -> crate::Result<BytesStart> {
+        // Inputs, submit buttons and file buttons belong to the innermost form they're in, if any.
+        let mut trim_bytes_start = |bs: &BytesStart,
+                                    warnings: &mut Vec<String>,
+                                    form: Option<&mut Form>|
+         -> crate::Result<BytesStart> {
             let attrs = attrs_map(bs)?;
             let tag_name = String::from_utf8(bs.name().local_name().into_inner().to_vec())?;
             let mut result = BytesStart::new(tag_name);
+            let mut target = None;
+            let mut signal = None;
+            let mut name = None;
+            let mut submit = false;
+            let mut file = None;
             for attr in bs.attributes() {
                 let attr = attr?;
                 match parse_web_tag(&attr.key) {
                     Some(web_tag) => {
                         let value = String::from_utf8(attr.value.to_vec())?;
                         match web_tag {
-                            b"href" => {
-                                let id = match attrs.get("id") {
-                                    Some(id) => id.clone(),
-                                    None => {
-                                        let class = attrs
-                                            .get("class")
-                                            .expect("expected 'class' attribute to be present");
-                                        let id = id_autogenerator.next(&class);
-                                        result.push_attribute(("id", id.as_str()));
-                                        id
-                                    }
-                                };
-                                hrefs.insert(id.to_string(), value);
-                            }
-                            k => println!(
+                            b"href" => target = Some(value),
+                            b"href-signal" => signal = Some(value),
+                            b"name" => name = Some(value),
+                            b"submit" => submit = true,
+                            b"file" => file = Some(value),
+                            k => warnings.push(format!(
                                 "unknown web attribute: {}",
                                 String::from_utf8(k.to_vec())?
-                            ),
+                            )),
                         }
                     }
                     None => {
@@ -82,6 +169,67 @@ impl Definition {
                     }
                 }
             }
+
+            if target.is_none() && signal.is_some() {
+                warnings.push(String::from("web:href-signal ignored without web:href"));
+            }
+            if (name.is_some() || submit || file.is_some()) && form.is_none() {
+                warnings.push(String::from(
+                    "web:name, web:submit and web:file ignored outside of a web:form",
+                ));
+                name = None;
+                submit = false;
+                file = None;
+            }
+            // Submit buttons already do something when clicked.
+            if submit && target.is_some() {
+                warnings.push(String::from("web:href ignored on a web:submit button"));
+                target = None;
+            }
+
+            let needs_id = [
+                ("web:href", target.is_some()),
+                ("web:name", name.is_some()),
+                ("web:submit", submit),
+                ("web:file", file.is_some()),
+            ];
+            if !needs_id.iter().any(|&(_, needed)| needed) {
+                return Ok(result);
+            }
+            let id = match (attrs.get("id"), attrs.get("class")) {
+                (Some(id), _) => id.clone(),
+                (None, Some(class)) => {
+                    let id = id_autogenerator.next(class);
+                    result.push_attribute(("id", id.as_str()));
+                    id
+                }
+                (None, None) => {
+                    let ignored = needs_id
+                        .iter()
+                        .filter(|&&(_, needed)| needed)
+                        .map(|&(attr, _)| attr)
+                        .collect::<Vec<_>>();
+                    warnings.push(format!(
+                        "{} ignored on an element without an id or class",
+                        ignored.join(" and ")
+                    ));
+                    return Ok(result);
+                }
+            };
+            if let Some(target) = target {
+                hrefs.insert(id.clone(), Href { target, signal });
+            }
+            if let Some(form) = form {
+                if let Some(name) = name {
+                    form.inputs.push((name, id.clone()));
+                }
+                if submit {
+                    form.submits.push(id.clone());
+                }
+                if let Some(file) = file {
+                    form.files.push((file, id));
+                }
+            }
             Ok(result)
         };
 
@@ -90,79 +238,432 @@ impl Definition {
         let mut reading_script = false;
         let mut current_script_type = None;
         let mut current_script = String::new();
+        let mut current_script_src = None;
 
         let mut reading_style = false;
+        let mut current_style = String::new();
+        let mut current_style_src = None;
+
+        // Indexes into `forms` of the forms that are open, innermost last.
+        let mut open_forms: Vec<usize> = Vec::new();
 
-        loop {
-            match reader.read_event_into(&mut buf)? {
-                Event::Eof => break,
-                Event::Start(ref bs) => match parse_web_tag(&bs.name()) {
-                    Some(SCRIPT_TAG) => {
-                        let attrs = attrs_map(bs)?;
-                        match attrs.get("type") {
-                            None => println!("script tag found, but no type was specified"),
-                            Some(r#type) => match crate::script::Lang::from(r#type) {
-                                None => println!("script tag found with unknown type '{}'", r#type),
-                                Some(lang) => {
-                                    current_script_type = Some(lang);
-                                    current_script = String::new();
-                                    reading_script = true;
+        // Errors are reported at the start of the event being processed, which is usually the
+        // element or text they're in.
+        let mut source_map = Vec::new();
+        let mut event_start = 0;
+        // Warnings are collected with the offset of the event they're for, and given a line and
+        // column once parsing is done.
+        let mut warnings_at = Vec::new();
+        let mut src_offsets = HashMap::new();
+        let mut process = || -> crate::Result<()> {
+            loop {
+                event_start = reader.buffer_position();
+                source_map.push((writer.get_ref().position() as usize, event_start));
+                let mut warnings = Vec::new();
+                match reader.read_event_into(&mut buf)? {
+                    Event::Eof => break,
+                    Event::Start(ref bs) => match parse_web_tag(&bs.name()) {
+                        Some(SCRIPT_TAG) => {
+                            let attrs = attrs_map(bs)?;
+                            if let Some(lang) = script_lang(&attrs, &mut warnings) {
+                                current_script_type = Some(lang);
+                                current_script = String::new();
+                                current_script_src = attrs.get("src").cloned();
+                                if let Some(src) = &current_script_src {
+                                    src_offsets.entry(src.clone()).or_insert(event_start);
                                 }
-                            },
+                                reading_script = true;
+                            }
                         }
-                    }
-                    Some(STYLE_TAG) => {
-                        reading_style = true;
-                    }
-                    _ => writer.write_event(Event::Start(trim_bytes_start(bs)?))?,
-                },
-                Event::Text(bt) => {
-                    if reading_script {
-                        current_script.push_str(&mut bt.unescape()?);
-                    } else if reading_style {
-                        styles.push_str(&mut bt.unescape()?);
-                    } else {
-                        writer.write_event(Event::Text(bt))?;
-                    }
-                }
-                Event::End(be) => match parse_web_tag(&be.name()) {
-                    Some(SCRIPT_TAG) => {
+                        Some(STYLE_TAG) => {
+                            current_style = String::new();
+                            current_style_src = attrs_map(bs)?.get("src").cloned();
+                            if let Some(src) = &current_style_src {
+                                src_offsets.entry(src.clone()).or_insert(event_start);
+                            }
+                            reading_style = true;
+                        }
+                        Some(FORM_TAG) => {
+                            open_forms.push(forms.len());
+                            forms.push(parse_form(&attrs_map(bs)?, &mut warnings));
+                        }
+                        _ => {
+                            let form = open_forms.last().map(|&i| &mut forms[i]);
+                            let bs = trim_bytes_start(bs, &mut warnings, form)?;
+                            writer.write_event(Event::Start(bs))?
+                        }
+                    },
+                    Event::Text(bt) => {
                         if reading_script {
-                            scripts.push(crate::script::Script::new(
-                                current_script_type.unwrap(),
-                                current_script.clone(),
-                            ));
-                            reading_script = false;
+                            current_script.push_str(&mut bt.unescape()?);
+                        } else if reading_style {
+                            current_style.push_str(&mut bt.unescape()?);
+                        } else {
+                            writer.write_event(Event::Text(bt))?;
                         }
                     }
-                    Some(STYLE_TAG) => {
-                        reading_style = false;
-                    }
-                    _ => writer.write_event(Event::End(be))?,
-                },
-                Event::Empty(ref bs) => match parse_web_tag(&bs.name()) {
-                    Some(PAGE_TAG) => {
-                        let attrs = attrs_map(bs)?;
-                        if let Some(v) = attrs.get("title") {
-                            title = Some(v.clone());
+                    Event::End(be) => match parse_web_tag(&be.name()) {
+                        Some(SCRIPT_TAG) => {
+                            if let (true, Some(lang)) = (reading_script, current_script_type) {
+                                // Like in HTML, the contents are ignored if there's a src attribute.
+                                scripts.push(match current_script_src.take() {
+                                    Some(src) => crate::script::Script::from_src(lang, src),
+                                    None => crate::script::Script::new(lang, current_script.clone()),
+                                });
+                                reading_script = false;
+                            }
                         }
-                    }
-                    _ => writer.write_event(Event::Empty(trim_bytes_start(bs)?))?,
-                },
-                e => writer.write_event(&e)?,
+                        Some(STYLE_TAG) => {
+                            if reading_style {
+                                styles.push(match current_style_src.take() {
+                                    Some(src) => Style {
+                                        src: Some(src),
+                                        text: String::new(),
+                                    },
+                                    None => Style {
+                                        src: None,
+                                        text: current_style.clone(),
+                                    },
+                                });
+                                reading_style = false;
+                            }
+                        }
+                        Some(FORM_TAG) => {
+                            open_forms.pop();
+                        }
+                        _ => writer.write_event(Event::End(be))?,
+                    },
+                    Event::Empty(ref bs) => match parse_web_tag(&bs.name()) {
+                        Some(PAGE_TAG) => {
+                            let attrs = attrs_map(bs)?;
+                            if let Some(v) = attrs.get("title") {
+                                title = Some(v.clone());
+                            }
+                            if let Some(v) = attrs.get("base") {
+                                base = Some(v.clone());
+                            }
+                        }
+                        Some(SCRIPT_TAG) => {
+                            let attrs = attrs_map(bs)?;
+                            if let (Some(lang), Some(src)) =
+                                (script_lang(&attrs, &mut warnings), attrs.get("src"))
+                            {
+                                src_offsets.entry(src.clone()).or_insert(event_start);
+                                scripts.push(crate::script::Script::from_src(lang, src.clone()));
+                            }
+                        }
+                        Some(STYLE_TAG) => {
+                            if let Some(src) = attrs_map(bs)?.get("src") {
+                                src_offsets.entry(src.clone()).or_insert(event_start);
+                                styles.push(Style {
+                                    src: Some(src.clone()),
+                                    text: String::new(),
+                                });
+                            }
+                        }
+                        Some(FORM_TAG) => {
+                            warnings.push(String::from("web:form ignored without any contents"));
+                        }
+                        _ => {
+                            let form = open_forms.last().map(|&i| &mut forms[i]);
+                            let bs = trim_bytes_start(bs, &mut warnings, form)?;
+                            writer.write_event(Event::Empty(bs))?
+                        }
+                    },
+                    e => writer.write_event(&e)?,
+                }
+                warnings_at.extend(warnings.into_iter().map(|warning| (event_start, warning)));
+                // quick-xml finds where comments end by looking at what's in the buffer, so
+                // leftovers from earlier events would make it misread ones like `<!-->`.
+                buf.clear();
             }
+            Ok(())
+        };
+        if let Err(err) = process() {
+            let (line, column) = line_column(&source, event_start);
+            return Err(crate::error::Error::ParseError {
+                line,
+                column,
+                message: err.to_string(),
+            });
         }
 
+        let diagnostics = warnings_at
+            .into_iter()
+            .map(|(offset, message)| {
+                let (line, column) = line_column(&source, offset);
+                Diagnostic {
+                    line,
+                    column,
+                    message,
+                }
+            })
+            .collect();
+
         let def = Definition {
             source,
             buildable: String::from_utf8(writer.into_inner().into_inner())?,
             hrefs,
+            forms,
             scripts,
             styles,
             title,
+            base,
+            diagnostics,
+            source_map,
+            src_offsets,
         };
         Ok(def)
     }
+
+    /// Converts an error from building the buildable with a GtkBuilder, which gives a position in
+    /// the buildable, into one that gives the same position in the source.
+    pub fn locate_builder_error(&self, err: glib::Error) -> crate::error::Error {
+        match builder_error_position(err.message()) {
+            Some((line, column, message)) => {
+                let (line, column) = self.source_position(line, column);
+                crate::error::Error::ParseError {
+                    line,
+                    column,
+                    message: message.to_string(),
+                }
+            }
+            None => err.into(),
+        }
+    }
+
+    /// Maps a line and column in the buildable to the same place in the source. Positions within
+    /// a tag that had web-specific attributes removed are approximate.
+    fn source_position(&self, line: usize, column: usize) -> (usize, usize) {
+        let offset = byte_offset(&self.buildable, line, column);
+        let index = self
+            .source_map
+            .partition_point(|&(buildable_offset, _)| buildable_offset <= offset);
+        let source_offset = match index.checked_sub(1).map(|i| self.source_map[i]) {
+            Some((buildable_offset, source_offset)) => source_offset + (offset - buildable_offset),
+            None => offset,
+        };
+        line_column(&self.source, source_offset.min(self.source.len()))
+    }
+
+    /// Returns true if any of the page's scripts or styles are loaded from a URL.
+    pub fn has_remote_sources(&self) -> bool {
+        self.scripts.iter().any(|script| script.src.is_some())
+            || self.styles.iter().any(|style| style.src.is_some())
+    }
+
+    /// Loads the text of every script and style that has a `src` attribute using `load`, which is
+    /// given the attribute's value. Any that fail to load are left out, as in a web browser, and
+    /// reported as diagnostics.
+    pub fn load_sources<F>(&mut self, mut load: F)
+    where
+        F: FnMut(&str) -> super::Result<String>,
+    {
+        for script in std::mem::take(&mut self.scripts) {
+            let text = match &script.src {
+                Some(src) => match load(src) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        let message = format!("failed to load script from {}: {}", src, err);
+                        self.push_src_diagnostic(src, message);
+                        continue;
+                    }
+                },
+                None => script.text,
+            };
+            self.scripts.push(crate::script::Script { text, ..script });
+        }
+
+        for style in std::mem::take(&mut self.styles) {
+            let text = match &style.src {
+                Some(src) => match load(src) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        let message = format!("failed to load style from {}: {}", src, err);
+                        self.push_src_diagnostic(src, message);
+                        continue;
+                    }
+                },
+                None => style.text,
+            };
+            self.styles.push(Style { text, ..style });
+        }
+    }
+
+    /// Reports a problem with a script or style at the element that its `src` came from.
+    fn push_src_diagnostic(&mut self, src: &str, message: String) {
+        let offset = self.src_offsets.get(src).copied().unwrap_or(0);
+        let (line, column) = line_column(&self.source, offset);
+        self.diagnostics.push(Diagnostic {
+            line,
+            column,
+            message,
+        });
+    }
+
+    /// Returns all of the page's styles combined, in document order.
+    pub fn stylesheet(&self) -> String {
+        self.styles
+            .iter()
+            .map(|style| style.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Copies the object with the given ID, along with everything inside of it, out of a processed UI
+/// definition so that it can be built again. Every object ID in the copy has `suffix` appended
+/// to it so that the copy can coexist with the original. Returns the copy as a standalone UI
+/// definition, and a map from the original object IDs to the new ones.
+pub fn clone_object(
+    buildable: &str,
+    id: &str,
+    suffix: &str,
+) -> super::Result<Option<(String, HashMap<String, String>)>> {
+    let mut reader = quick_xml::Reader::from_str(buildable);
+    let mut writer = quick_xml::Writer::new(Cursor::new(Vec::new()));
+    let mut ids = HashMap::new();
+    let mut buf = Vec::new();
+
+    // The number of open elements within the object being cloned, or 0 if it hasn't been found.
+    let mut depth = 0;
+
+    writer.write_event(Event::Start(BytesStart::new("interface")))?;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Eof => return Ok(None),
+            Event::Start(bs) => {
+                if depth > 0 || object_id(&bs)?.as_deref() == Some(id) {
+                    depth += 1;
+                    writer.write_event(Event::Start(with_id_suffix(&bs, suffix, &mut ids)?))?;
+                }
+            }
+            Event::Empty(bs) => {
+                if depth > 0 {
+                    writer.write_event(Event::Empty(with_id_suffix(&bs, suffix, &mut ids)?))?;
+                } else if object_id(&bs)?.as_deref() == Some(id) {
+                    writer.write_event(Event::Empty(with_id_suffix(&bs, suffix, &mut ids)?))?;
+                    break;
+                }
+            }
+            Event::End(be) => {
+                if depth > 0 {
+                    writer.write_event(Event::End(be))?;
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            e => {
+                if depth > 0 {
+                    writer.write_event(e)?;
+                }
+            }
+        }
+        buf.clear();
+    }
+    writer.write_event(Event::End(BytesEnd::new("interface")))?;
+
+    Ok(Some((
+        String::from_utf8(writer.into_inner().into_inner())?,
+        ids,
+    )))
+}
+
+/// Reads the action and method of a `<web:form>` element. Like in HTML, forms are submitted to
+/// the page itself with GET unless they say otherwise.
+fn parse_form(attrs: &HashMap<String, String>, warnings: &mut Vec<String>) -> Form {
+    let method = match attrs.get("method") {
+        None => String::from("GET"),
+        Some(method) if method.eq_ignore_ascii_case("get") => String::from("GET"),
+        Some(method) if method.eq_ignore_ascii_case("post") => String::from("POST"),
+        Some(method) => {
+            warnings.push(format!("unknown form method '{}', using GET", method));
+            String::from("GET")
+        }
+    };
+    Form {
+        action: attrs.get("action").cloned().unwrap_or_default(),
+        method,
+        inputs: Vec::new(),
+        submits: Vec::new(),
+        files: Vec::new(),
+    }
+}
+
+/// Returns the ID of an `object` element, if it has one.
+fn object_id(bs: &BytesStart) -> super::Result<Option<String>> {
+    if bs.name().as_ref() != b"object" {
+        return Ok(None);
+    }
+    for attr in bs.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == b"id" {
+            return Ok(Some(String::from_utf8(attr.value.into_owned())?));
+        }
+    }
+    Ok(None)
+}
+
+fn with_id_suffix(
+    bs: &BytesStart,
+    suffix: &str,
+    ids: &mut HashMap<String, String>,
+) -> super::Result<BytesStart<'static>> {
+    let old_id = object_id(bs)?;
+    let mut result = BytesStart::new(String::from_utf8(bs.name().as_ref().to_vec())?);
+    for attr in bs.attributes() {
+        let attr = attr?;
+        match old_id {
+            Some(ref old_id) if attr.key.as_ref() == b"id" => {
+                let new_id = format!("{}{}", old_id, suffix);
+                result.push_attribute(("id", new_id.as_str()));
+                ids.insert(old_id.clone(), new_id);
+            }
+            _ => result.push_attribute(attr),
+        }
+    }
+    Ok(result)
+}
+
+/// Splits the position off of a GtkBuilder error message, which starts with one like
+/// `<input>:3:15 `.
+fn builder_error_position(message: &str) -> Option<(usize, usize, &str)> {
+    let rest = message.strip_prefix("<input>:")?;
+    let (position, message) = rest.split_once(' ')?;
+    let (line, column) = position.split_once(':')?;
+    Some((line.parse().ok()?, column.parse().ok()?, message))
+}
+
+/// Returns the 1-based line and column of a byte offset into `text`, counting columns in
+/// characters.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Returns the byte offset into `text` of a 1-based line and column, counting columns in
+/// characters. Positions past the end of a line or the text are clamped to it.
+fn byte_offset(text: &str, line: usize, column: usize) -> usize {
+    let line_start = match line.checked_sub(1) {
+        Some(0) | None => 0,
+        Some(n) => text.match_indices('\n').nth(n - 1).map_or(text.len(), |(i, _)| i + 1),
+    };
+    let line_text = text[line_start..].split('\n').next().unwrap_or("");
+    line_start
+        + line_text
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map_or(line_text.len(), |(i, _)| i)
 }
 
 fn parse_web_tag<'a>(name: &'a QName) -> Option<&'a [u8]> {
@@ -172,7 +673,7 @@ fn parse_web_tag<'a>(name: &'a QName) -> Option<&'a [u8]> {
     }
 }
 
-struct IdAutogenerator(HashMap<String, i8>);
+struct IdAutogenerator(HashMap<String, usize>);
 
 impl IdAutogenerator {
     fn new() -> Self {
@@ -201,14 +702,306 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    pub fn test_page_attrs() -> crate::Result<()> {
+        let body = r#"<interface><web:page title="Docs" base="/docs/"/></interface>"#;
+        let def = Definition::new(body.to_string())?;
+        assert_eq!(def.title.as_deref(), Some("Docs"));
+        assert_eq!(def.base.as_deref(), Some("/docs/"));
+        assert_eq!(def.buildable, "<interface></interface>");
+        Ok(())
+    }
+
     #[test]
     pub fn test_parse_href() -> crate::Result<()> {
-        let body = r#"<interface><object id="button" web:href="/some/page" /></interface>"#;
+        let body = r#"<interface>
+            <object id="button" web:href="/some/page" />
+            <object class="GtkListBox" web:href-signal="row-selected" web:href="/list" />
+            <object class="GtkLabel" web:href-signal="activate-link" />
+        </interface>"#;
         let def = Definition::new(body.to_string())?;
         assert_eq!(
             def.hrefs,
-            HashMap::from([(String::from("button"), String::from("/some/page"))])
+            HashMap::from([
+                (
+                    String::from("button"),
+                    Href {
+                        target: String::from("/some/page"),
+                        signal: None,
+                    }
+                ),
+                (
+                    String::from("GtkListBox-1"),
+                    Href {
+                        target: String::from("/list"),
+                        signal: Some(String::from("row-selected")),
+                    }
+                ),
+            ])
         );
+        assert_eq!(
+            def.diagnostics[0].message,
+            "web:href-signal ignored without web:href"
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_parse_forms() -> crate::Result<()> {
+        let body = r#"<interface>
+<object class="GtkBox" id="body">
+  <child>
+    <web:form action="/login" method="post">
+      <object class="GtkBox">
+        <child><object class="GtkEntry" id="username" web:name="username"/></child>
+        <child><object class="GtkPasswordEntry" web:name="password"/></child>
+        <child><object class="GtkButton" web:submit="true"/></child>
+        <child><object class="GtkButton" id="photos" web:file="photo"/></child>
+      </object>
+    </web:form>
+  </child>
+  <child>
+    <web:form method="delete">
+      <object class="GtkCheckButton" web:name="remember"/>
+    </web:form>
+  </child>
+  <child><object class="GtkEntry" web:name="stray"/></child>
+</object>
+</interface>"#;
+        let def = Definition::new(body.to_string())?;
+        assert_eq!(
+            def.forms,
+            vec![
+                Form {
+                    action: String::from("/login"),
+                    method: String::from("POST"),
+                    inputs: vec![
+                        (String::from("username"), String::from("username")),
+                        (String::from("password"), String::from("GtkPasswordEntry-1")),
+                    ],
+                    submits: vec![String::from("GtkButton-1")],
+                    files: vec![(String::from("photo"), String::from("photos"))],
+                },
+                Form {
+                    action: String::new(),
+                    method: String::from("GET"),
+                    inputs: vec![(String::from("remember"), String::from("GtkCheckButton-1"))],
+                    submits: vec![],
+                    files: vec![],
+                },
+            ]
+        );
+        assert!(!def.buildable.contains("form"));
+        assert!(!def.buildable.contains("web:"));
+        assert_eq!(
+            def.diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "line 14, column 5: unknown form method 'delete', using GET",
+                "line 18, column 10: web:name, web:submit and web:file ignored outside of a web:form",
+            ]
+        );
+
+        let body = r#"<interface>
+<web:form action="/login">
+  <object class="GtkButton" id="body" web:submit="true" web:href="/help"/>
+</web:form>
+</interface>"#;
+        let def = Definition::new(body.to_string())?;
+        assert_eq!(def.forms[0].submits, vec![String::from("body")]);
+        assert!(def.hrefs.is_empty());
+        assert_eq!(
+            def.diagnostics[0].to_string(),
+            "line 3, column 3: web:href ignored on a web:submit button"
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_parse_error_position() {
+        let body = "<interface>\n  <object class=\"GtkBox\">\n  </interface>\n";
+        match Definition::new(body.to_string()) {
+            Err(crate::error::Error::ParseError { line, column, .. }) => {
+                assert_eq!((line, column), (3, 3))
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    pub fn test_source_position() -> crate::Result<()> {
+        let body = r#"<interface>
+  <web:script type="lua">
+    print("hé")
+  </web:script>
+  <object class="GtkLabel" id="body" web:href="/next">
+    <property name="label">Next</property>
+  </object>
+</interface>"#;
+        let def = Definition::new(body.to_string())?;
+        assert_eq!(def.source_position(1, 1), (1, 1));
+        assert_eq!(def.source_position(3, 3), (5, 3));
+        assert_eq!(def.source_position(4, 5), (6, 5));
+        assert_eq!(def.source_position(100, 1), (8, 13));
+
+        assert_eq!(
+            builder_error_position("<input>:4:5 Invalid property: GtkLabel.labl"),
+            Some((4, 5, "Invalid property: GtkLabel.labl"))
+        );
+        assert_eq!(builder_error_position("Invalid object type 'GtkFoo'"), None);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_diagnostics() -> crate::Result<()> {
+        let body = r#"<interface>
+<object web:href="/nowhere"/>
+<object class="GtkButton" web:href="/somewhere" web:clicked="x"/>
+<web:script type="python">print()</web:script>
+</interface>"#;
+        let def = Definition::new(body.to_string())?;
+        assert_eq!(
+            def.hrefs,
+            HashMap::from([(
+                String::from("GtkButton-1"),
+                Href {
+                    target: String::from("/somewhere"),
+                    signal: None,
+                }
+            )])
+        );
+        assert_eq!(
+            def.diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "line 2, column 1: web:href ignored on an element without an id or class",
+                "line 3, column 1: unknown web attribute: clicked",
+                "line 4, column 1: script tag found with unknown type 'python'",
+            ]
+        );
+        Ok(())
+    }
+
+    /// Parses a large number of random and mangled definitions, which must fail with an error
+    /// rather than panicking.
+    #[test]
+    pub fn test_parse_fuzz() {
+        const PIECES: &[&str] = &[
+            "<", ">", "/>", "</", "=", "\"", "'", "&", "&amp;", "&#x0;", "\n", " ", "é", "\u{0}",
+            "interface", "object", "child", "property", "web:", "web:href", "web:page", "web:script",
+            "web:style", "id", "class", "type", "src", "title", "lua", "GtkButton", "body", "<!--",
+            "-->", "<!-->", "<![CDATA[", "]]>", "<?xml", "?>", "xmlns:web",
+        ];
+        let valid = r#"<interface>
+  <web:page title="Page"/>
+  <web:script type="lua">find_widget("button")</web:script>
+  <web:style>label { color: red; }</web:style>
+  <object class="GtkButton" id="body" web:href="/next"><property name="label">Next</property></object>
+</interface>"#;
+
+        // A simple xorshift generator, so that failures can be reproduced.
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize
+        };
+        let check = |source: String| {
+            if let Ok(def) = Definition::new(source) {
+                for line in 0..4 {
+                    def.source_position(line, line * 7);
+                }
+            }
+        };
+
+        for _ in 0..2000 {
+            let len = next() % 40;
+            check((0..len).map(|_| PIECES[next() % PIECES.len()]).collect());
+        }
+        for end in 0..=valid.len() {
+            if valid.is_char_boundary(end) {
+                check(valid[..end].to_string());
+            }
+        }
+        for _ in 0..2000 {
+            let mut source = valid.to_string();
+            let mut at = next() % source.len();
+            while !source.is_char_boundary(at) {
+                at -= 1;
+            }
+            source.insert_str(at, PIECES[next() % PIECES.len()]);
+            check(source);
+        }
+        assert!(Definition::new(String::from("<interface><!--></interface>")).is_err());
+        assert!(Definition::new(String::from("<interface><!-- <!--> --></interface>")).is_ok());
+        let def = Definition::new(String::from(
+            r#"<interface><object class="GtkLabel"><property name="label"><![CDATA[<!-->]]></property></object></interface>"#,
+        ))
+        .unwrap();
+        assert!(def.buildable.contains("<![CDATA[<!-->]]>"));
+    }
+
+    #[test]
+    pub fn test_load_sources() -> crate::Result<()> {
+        let body = r#"<interface>
+            <web:script type="lua" src="first.lua"/>
+            <web:script type="lua">second()</web:script>
+            <web:script type="lua" src="missing.lua"></web:script>
+            <web:style src="theme.css"/>
+            <web:style>label { color: red; }</web:style>
+        </interface>"#;
+        let mut def = Definition::new(body.to_string())?;
+        assert!(def.has_remote_sources());
+
+        def.load_sources(|src| match src {
+            "missing.lua" => Err(crate::error::Error::Any(String::from("not found"))),
+            src => Ok(format!("-- {}", src)),
+        });
+        assert_eq!(
+            def.scripts.iter().map(|script| script.text.as_str()).collect::<Vec<_>>(),
+            vec!["-- first.lua", "second()"]
+        );
+        assert_eq!(def.stylesheet(), "-- theme.css\nlabel { color: red; }");
+        assert_eq!(
+            def.diagnostics,
+            vec![Diagnostic {
+                line: 4,
+                column: 13,
+                message: String::from("failed to load script from missing.lua: not found"),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_clone_object() -> crate::Result<()> {
+        let buildable = r#"<interface><object class="GtkBox" id="body"><child><object class="GtkBox" id="row"><child><object class="GtkLabel" id="name"><property name="label">Name</property></object></child><child><object class="GtkButton"/></child></object></child></object></interface>"#;
+
+        let (ui, ids) = clone_object(buildable, "row", "-1")?.unwrap();
+        assert_eq!(
+            ui,
+            r#"<interface><object class="GtkBox" id="row-1"><child><object class="GtkLabel" id="name-1"><property name="label">Name</property></object></child><child><object class="GtkButton"/></child></object></interface>"#
+        );
+        assert_eq!(
+            ids,
+            HashMap::from([
+                (String::from("row"), String::from("row-1")),
+                (String::from("name"), String::from("name-1")),
+            ])
+        );
+
+        let (ui, _) = clone_object(buildable, "name", "-2")?.unwrap();
+        assert_eq!(
+            ui,
+            r#"<interface><object class="GtkLabel" id="name-2"><property name="label">Name</property></object></interface>"#
+        );
+
+        assert!(clone_object(buildable, "missing", "-1")?.is_none());
         Ok(())
     }
 
@@ -1,69 +1,136 @@
-pub fn absolutize_url(current_location: &String, target: &String) -> String {
-    if target.is_empty() {
-        return current_location.clone();
+use std::path::PathBuf;
+
+/// Turns a location typed by the user into one that can be navigated to. Bare filesystem paths
+/// are converted into `file://` URLs; anything else is returned unchanged.
+pub fn normalize_location(location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let path = match std::fs::canonicalize(location) {
+        Ok(path) => path,
+        Err(_) if location.starts_with("/") => PathBuf::from(location),
+        Err(_) => return location.to_string(),
+    };
+    match url::Url::from_file_path(&path) {
+        Ok(url) => url.to_string(),
+        Err(()) => location.to_string(),
     }
-    if target.contains("://") {
-        return target.clone();
+}
+
+/// Returns the filesystem path referenced by a `file://` location, if it is one.
+pub fn file_path(location: &str) -> Option<PathBuf> {
+    match url::Url::parse(location) {
+        Ok(url) if url.scheme() == "file" => url.to_file_path().ok(),
+        _ => None,
     }
-    if !target.starts_with("/") {
-        let mut result = String::new();
-        result.push_str(current_location);
-        result.push_str("/");
-        result.push_str(target);
-        return result;
+}
+
+/// Resolves a link target against the URL of the page it's on, the way a web browser does: an
+/// absolute URL is used as is, and anything else is joined to the base following RFC 3986. If
+/// the base can't have anything joined to it, like `about:blank`, a relative target is returned
+/// unchanged.
+pub fn resolve_url(base: &str, target: &str) -> String {
+    let url = match url::Url::parse(base) {
+        Ok(base) => base.join(target),
+        Err(_) => url::Url::parse(target),
+    };
+    match url {
+        Ok(url) => url.to_string(),
+        Err(_) => target.to_string(),
     }
-    match current_location.find("://") {
-        Some(idx) => {
-            let mut result = String::new();
-            if let Some(root) = current_location[idx + 3..].find("/") {
-                result.push_str(&current_location[0..root + idx + 3]);
-            } else {
-                result.push_str(&current_location);
+}
+
+/// Returns true if going from `current` to `target` only moves to another part of the same page,
+/// like following a link to `#settings`, so the page doesn't need to be loaded again. Like in a
+/// browser, going to a location without a fragment always loads it.
+pub fn is_fragment_navigation(current: &str, target: &str) -> bool {
+    match (url::Url::parse(current), url::Url::parse(target)) {
+        (Ok(mut current), Ok(mut target)) => {
+            if target.fragment().is_none() {
+                return false;
             }
-            result.push_str(target);
-            result
+            current.set_fragment(None);
+            target.set_fragment(None);
+            current == target
         }
-        None => unimplemented!(),
+        _ => false,
     }
 }
 
+/// Returns the fragment of a location, without the `#`, if it has a non-empty one.
+pub fn fragment(location: &str) -> Option<String> {
+    url::Url::parse(location)
+        .ok()?
+        .fragment()
+        .filter(|fragment| !fragment.is_empty())
+        .map(String::from)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
-    pub fn test_absolutize_url() {
+    pub fn test_resolve_url() {
+        for (base, target, expected) in [
+            ("", "http://localhost:8000", "http://localhost:8000/"),
+            ("http://localhost:8000", "/sub-page", "http://localhost:8000/sub-page"),
+            ("http://localhost:8000/sub-page", "/another-page", "http://localhost:8000/another-page"),
+            ("http://localhost:8000/sub-page", "sub-sub-page", "http://localhost:8000/sub-sub-page"),
+            ("http://localhost:8000/sub-page/", "sub-sub-page", "http://localhost:8000/sub-page/sub-sub-page"),
+            ("http://localhost:8000/dir/page?q=1", "other", "http://localhost:8000/dir/other"),
+            ("http://localhost:8000/a/b/c", "../d", "http://localhost:8000/a/d"),
+            ("http://localhost:8000/page?q=1#top", "?q=2", "http://localhost:8000/page?q=2"),
+            ("http://localhost:8000/page?q=1", "#section", "http://localhost:8000/page?q=1#section"),
+            ("http://localhost:8000/current-page", "", "http://localhost:8000/current-page"),
+            ("http://localhost:8000/page#top", "", "http://localhost:8000/page"),
+            ("http://localhost:8000/", "//example.com/x", "http://example.com/x"),
+            ("https://localhost:8000/", "mailto:webby@example.com", "mailto:webby@example.com"),
+            ("file:///home/user/site/index.ui", "about.ui", "file:///home/user/site/about.ui"),
+            ("file:///home/user/site/index.ui", "/tmp/other.ui", "file:///tmp/other.ui"),
+            ("about:blank", "about:history", "about:history"),
+            ("about:blank", "/page", "/page"),
+            ("localhost:8000", "page", "page"),
+        ] {
+            assert_eq!(resolve_url(base, target), expected, "{} + {}", base, target);
+        }
+    }
+
+    #[test]
+    pub fn test_fragments() {
+        let page = "http://localhost:8000/page?q=1";
+        assert!(is_fragment_navigation(page, "http://localhost:8000/page?q=1#settings"));
+        assert!(!is_fragment_navigation("http://localhost:8000/page?q=1#a", page));
+        assert!(is_fragment_navigation(
+            "http://localhost:8000/page?q=1#a",
+            "http://localhost:8000/page?q=1#a"
+        ));
+        assert!(!is_fragment_navigation(page, page));
+        assert!(!is_fragment_navigation(page, "http://localhost:8000/page?q=2#a"));
+        assert!(!is_fragment_navigation("about:blank", "http://localhost:8000/#a"));
+        assert!(!is_fragment_navigation("", "#a"));
+
+        assert_eq!(fragment("http://localhost:8000/#settings"), Some(String::from("settings")));
+        assert_eq!(fragment("http://localhost:8000/#"), None);
+        assert_eq!(fragment("http://localhost:8000/"), None);
+        assert_eq!(fragment("#settings"), None);
+    }
+
+    #[test]
+    pub fn test_file_locations() {
         assert_eq!(
-            absolutize_url(&String::new(), &String::from("http://localhost:8000")),
+            normalize_location("http://localhost:8000"),
             "http://localhost:8000"
         );
         assert_eq!(
-            absolutize_url(
-                &String::from("http://localhost:8000"),
-                &String::from("/sub-page")
-            ),
-            "http://localhost:8000/sub-page"
-        );
-        assert_eq!(
-            absolutize_url(
-                &String::from("http://localhost:8000/sub-page"),
-                &String::from("/another-page")
-            ),
-            "http://localhost:8000/another-page"
-        );
-        assert_eq!(
-            absolutize_url(
-                &String::from("http://localhost:8000/sub-page"),
-                &String::from("sub-sub-page")
-            ),
-            "http://localhost:8000/sub-page/sub-sub-page"
+            normalize_location("/does/not/exist.ui"),
+            "file:///does/not/exist.ui"
         );
+        assert_eq!(normalize_location("localhost:8000"), "localhost:8000");
         assert_eq!(
-            absolutize_url(
-                &String::from("http://localhost:8000/current-page"),
-                &String::from("")
-            ),
-            "http://localhost:8000/current-page"
+            file_path("file:///home/user/index.ui"),
+            Some(PathBuf::from("/home/user/index.ui"))
         );
+        assert_eq!(file_path("http://localhost:8000/index.ui"), None);
     }
 }
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub type SharedVisits = Arc<Mutex<Visits>>;
+
+/// The most URLs remembered. Once there are more, the least recently visited are forgotten.
+pub const MAX_SIZE: usize = 5000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Visit {
+    pub url: String,
+    pub title: Option<String>,
+    /// Time of the most recent visit, in seconds since the Unix epoch.
+    pub last_visited: u64,
+    pub count: u32,
+}
+
+/// Every location visited from any window or tab, with one entry per URL. Unlike a tab's
+/// navigation history, this is saved to disk and kept between sessions.
+#[derive(Debug, Default)]
+pub struct Visits {
+    /// File that the visits are saved to. If there is none, they aren't saved.
+    path: Option<PathBuf>,
+    /// Visits ordered from least to most recent.
+    list: Vec<Visit>,
+    /// Whether the visits have changed since they were loaded or last saved.
+    changed: bool,
+}
+
+impl Visits {
+    /// Loads visits from a JSON file. It doesn't need to exist yet; it will be created the first
+    /// time they're saved.
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let list = match std::fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Visits {
+            path: Some(path),
+            list,
+            changed: false,
+        })
+    }
+
+    pub fn save(&self) -> crate::Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, serde_json::to_string(&self.list)?)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the visits if they've changed since they were loaded or last saved. Visits are
+    /// recorded on every navigation, so they're saved in batches rather than one at a time.
+    pub fn save_changes(&mut self) -> crate::Result<()> {
+        if self.changed {
+            self.save()?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+
+    /// Records a visit to a URL at the current time.
+    pub fn record(&mut self, url: String) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        self.record_at(url, now);
+    }
+
+    fn record_at(&mut self, url: String, time: u64) {
+        let mut visit = match self.list.iter().position(|visit| visit.url == url) {
+            Some(index) => self.list.remove(index),
+            None => Visit {
+                url,
+                title: None,
+                last_visited: 0,
+                count: 0,
+            },
+        };
+        visit.last_visited = time;
+        visit.count += 1;
+        self.list.push(visit);
+        self.changed = true;
+
+        if self.list.len() > MAX_SIZE {
+            self.list.drain(..self.list.len() - MAX_SIZE);
+        }
+    }
+
+    /// Sets the title of a visited URL, returning true if it changed.
+    pub fn set_title(&mut self, url: &str, title: String) -> bool {
+        match self.list.iter_mut().find(|visit| visit.url == url) {
+            Some(visit) if visit.title.as_ref() != Some(&title) => {
+                visit.title = Some(title);
+                self.changed = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns up to `limit` visits whose URL or title contains every word in `query`, ignoring
+    /// case, with the most recent first. An empty query matches every visit.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&Visit> {
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        self.list
+            .iter()
+            .rev()
+            .filter(|visit| {
+                let url = visit.url.to_lowercase();
+                let title = visit.title.as_deref().unwrap_or("").to_lowercase();
+                words
+                    .iter()
+                    .all(|word| url.contains(word) || title.contains(word))
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// Removes the visit for a URL, returning false if there isn't one.
+    pub fn remove(&mut self, url: &str) -> bool {
+        let len = self.list.len();
+        self.list.retain(|visit| visit.url != url);
+        if self.list.len() == len {
+            return false;
+        }
+        self.changed = true;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.changed = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn urls(visits: &[&Visit]) -> Vec<String> {
+        visits.iter().map(|visit| visit.url.clone()).collect()
+    }
+
+    #[test]
+    pub fn test_record() {
+        let mut visits = Visits::default();
+        visits.record_at(String::from("http://localhost:8000/"), 1);
+        visits.record_at(String::from("http://example.com/"), 2);
+        visits.record_at(String::from("http://localhost:8000/"), 3);
+        assert!(visits.changed);
+        assert!(visits.set_title("http://localhost:8000/", String::from("Home")));
+        assert!(!visits.set_title("http://localhost:8000/", String::from("Home")));
+        assert!(!visits.set_title("http://localhost:8000/missing", String::from("Missing")));
+
+        assert_eq!(
+            visits.search("", 10),
+            vec![
+                &Visit {
+                    url: String::from("http://localhost:8000/"),
+                    title: Some(String::from("Home")),
+                    last_visited: 3,
+                    count: 2,
+                },
+                &Visit {
+                    url: String::from("http://example.com/"),
+                    title: None,
+                    last_visited: 2,
+                    count: 1,
+                },
+            ]
+        );
+
+        for i in 0..MAX_SIZE {
+            visits.record_at(format!("http://localhost:8000/{}", i), 4);
+        }
+        assert_eq!(visits.list.len(), MAX_SIZE);
+        assert!(visits.search("example", 10).is_empty());
+    }
+
+    #[test]
+    pub fn test_search() {
+        let mut visits = Visits::default();
+        visits.record_at(String::from("http://localhost:8000/todo"), 1);
+        visits.set_title("http://localhost:8000/todo", String::from("Todo List"));
+        visits.record_at(String::from("http://localhost:8000/"), 2);
+        visits.record_at(String::from("http://example.com/list"), 3);
+
+        assert_eq!(
+            urls(&visits.search("LIST", 10)),
+            vec!["http://example.com/list", "http://localhost:8000/todo"]
+        );
+        assert_eq!(urls(&visits.search("localhost todo", 10)), vec!["http://localhost:8000/todo"]);
+        assert_eq!(urls(&visits.search("", 1)), vec!["http://example.com/list"]);
+
+        assert!(visits.remove("http://example.com/list"));
+        assert!(!visits.remove("http://example.com/list"));
+        visits.clear();
+        assert!(visits.search("", 10).is_empty());
+    }
+}
@@ -1,43 +1,52 @@
-use std::io::Read;
 use std::sync::{Arc, Mutex};
 
-use glib::{clone, Continue, MainContext, PRIORITY_DEFAULT};
+use glib::clone;
 use gtk::prelude::*;
-use gtk::{gdk, gio, glib};
+use gtk::{gdk, gio, glib, pango};
+
+use crate::bookmarks::{Bookmarks, SharedBookmarks};
+use crate::tab::Tab;
+use crate::visits::SharedVisits;
+
+/// The most previously visited pages suggested while typing in the address bar.
+const MAX_ADDRESS_SUGGESTIONS: usize = 8;
 
 pub type WindowList = Arc<Mutex<Vec<Arc<Window>>>>;
 
 pub struct Window {
     #[allow(dead_code)]
     pub app_window: gtk::ApplicationWindow,
+    notebook: gtk::Notebook,
     back_button: gtk::Button,
     forward_button: gtk::Button,
     refresh_button: gtk::Button,
-    bookmark_button: gtk::Button,
+    bookmark_button: gtk::MenuButton,
+    bookmark_popover: gtk::Popover,
+    bookmark_title_entry: gtk::Entry,
     address_entry: gtk::Entry,
-    content: gtk::ScrolledWindow,
-    info_bar: gtk::InfoBar,
-    info_bar_text: gtk::Label,
-    status_label: gtk::Label,
-    pub state: Mutex<State>,
-}
-
-
-pub struct State {
-    pub globals: &'static crate::Globals,
-    pub location: String,
-    pub http_client: reqwest::blocking::Client,
-    pub builder: gtk::Builder,
-    pub ui_definition: Option<crate::ui::Definition>,
-    history: crate::history::History,
-    user_styles: Option<gtk::CssProvider>,
+    address_popover: gtk::Popover,
+    address_suggestions: gtk::ListBox,
+    pub bookmarks: SharedBookmarks,
+    bookmarks_menu: gio::Menu,
+    pub cookie_jar: Arc<crate::cookies::Jar>,
+    visits: SharedVisits,
+    /// Client shared by every tab in the window.
+    http_client: reqwest::blocking::Client,
+    tabs: Mutex<Vec<Arc<Tab>>>,
 }
 
 unsafe impl Send for Window {}
 unsafe impl Sync for Window {}
 
 impl Window {
-    pub fn new(app: &gtk::Application, globals: &'static crate::Globals) -> Arc<Self> {
+    pub fn new(
+        app: &gtk::Application,
+        root_certs: &[reqwest::tls::Certificate],
+        bookmarks: SharedBookmarks,
+        bookmarks_menu: gio::Menu,
+        cookie_jar: Arc<crate::cookies::Jar>,
+        visits: SharedVisits,
+    ) -> Arc<Self> {
         // Icon names are documented here: https://specifications.freedesktop.org/icon-naming-spec/icon-naming-spec-latest.html
         let back_button = gtk::Button::from_icon_name("go-previous");
         back_button.set_sensitive(false);
@@ -48,13 +57,99 @@ impl Window {
         let refresh_button = gtk::Button::from_icon_name("view-refresh");
         refresh_button.set_sensitive(false);
 
-        let bookmark_button = gtk::Button::from_icon_name("bookmark-new"); // no idea where this one is documented
+        // Opening the popover bookmarks the current page, and lets the bookmark be renamed or
+        // removed.
+        let bookmark_title_entry = gtk::Entry::new();
+        let bookmark_remove_button = gtk::Button::with_label("Remove");
+        let bookmark_done_button = gtk::Button::with_label("Done");
+        bookmark_done_button.set_hexpand(true);
+        bookmark_done_button.set_halign(gtk::Align::End);
+        let bookmark_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        bookmark_buttons.append(&bookmark_remove_button);
+        bookmark_buttons.append(&bookmark_done_button);
+        let bookmark_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        bookmark_box.append(&gtk::Label::new(Some("Bookmark")));
+        bookmark_box.append(&bookmark_title_entry);
+        bookmark_box.append(&bookmark_buttons);
+        let bookmark_popover = gtk::Popover::builder().child(&bookmark_box).build();
+
+        let bookmark_button = gtk::MenuButton::builder()
+            .icon_name("bookmark-new") // no idea where this one is documented
+            .popover(&bookmark_popover)
+            .sensitive(false)
+            .build();
 
         let address_entry = gtk::Entry::new();
         address_entry.set_property("placeholder-text", "Enter URL");
         address_entry.set_hexpand(true);
         //address_entry.set_text("http://localhost:8000"); // for testing
 
+        // Suggest previously visited pages, by URL and title, in a popover below the address bar.
+        // It doesn't hide itself, so that the address keeps the focus while it's being typed.
+        let address_suggestions = gtk::ListBox::new();
+        let address_popover = gtk::Popover::builder()
+            .child(&address_suggestions)
+            .autohide(false)
+            .has_arrow(false)
+            .position(gtk::PositionType::Bottom)
+            .halign(gtk::Align::Start)
+            .build();
+        address_popover.set_parent(&address_entry);
+        address_entry.connect_destroy(clone!(@weak address_popover => move |_| {
+            address_popover.unparent();
+        }));
+        address_entry.connect_changed(
+            clone!(@strong visits, @weak address_popover, @weak address_suggestions => move |address_entry| {
+                while let Some(row) = address_suggestions.row_at_index(0) {
+                    address_suggestions.remove(&row);
+                }
+                let text = address_entry.text();
+                if !text.is_empty() {
+                    for visit in visits.lock().unwrap().search(&text, MAX_ADDRESS_SUGGESTIONS) {
+                        address_suggestions
+                            .append(&address_suggestion_row(&visit.url, visit.title.as_deref()));
+                    }
+                }
+                if address_suggestions.row_at_index(0).is_some() {
+                    address_popover.popup();
+                } else {
+                    address_popover.popdown();
+                }
+            }),
+        );
+
+        // The arrow keys move through the suggestions without taking the focus from the address.
+        let address_keys = gtk::EventControllerKey::new();
+        address_keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        address_keys.connect_key_pressed(
+            clone!(@weak address_popover, @weak address_suggestions => @default-return gtk::Inhibit(false), move |_, key, _, _| {
+                if !address_popover.is_visible() {
+                    return gtk::Inhibit(false);
+                }
+                let selected = address_suggestions.selected_row().map(|row| row.index());
+                let index = match key {
+                    gdk::Key::Down => selected.map_or(0, |index| index + 1),
+                    gdk::Key::Up => selected.map_or(-1, |index| index - 1),
+                    gdk::Key::Escape => {
+                        address_popover.popdown();
+                        return gtk::Inhibit(true);
+                    }
+                    _ => return gtk::Inhibit(false),
+                };
+                address_suggestions.select_row(address_suggestions.row_at_index(index).as_ref());
+                gtk::Inhibit(true)
+            }),
+        );
+        address_entry.add_controller(address_keys);
+
+        // Hide the suggestions once the focus moves elsewhere, since the popover won't. Focus on
+        // a suggestion still counts as being within the address bar, which the popover belongs to.
+        let address_focus = gtk::EventControllerFocus::new();
+        address_focus.connect_leave(clone!(@weak address_popover => move |_| {
+            address_popover.popdown();
+        }));
+        address_entry.add_controller(address_focus);
+
         let top_bar = gtk::Box::new(gtk::Orientation::Horizontal, 6);
         top_bar.append(&back_button);
         top_bar.append(&forward_button);
@@ -62,29 +157,15 @@ impl Window {
         top_bar.append(&address_entry);
         top_bar.append(&bookmark_button);
 
-        let content = gtk::ScrolledWindow::builder()
+        let notebook = gtk::Notebook::builder()
+            .scrollable(true)
             .hexpand(true)
             .vexpand(true)
             .build();
 
-        let info_bar = gtk::InfoBar::builder()
-            .revealed(false)
-            .show_close_button(true)
-            .build();
-        info_bar.connect_response(move |this, response| match response {
-            gtk::ResponseType::Close => this.set_revealed(false),
-            _ => (),
-        });
-        let info_bar_text = gtk::Label::new(None);
-        info_bar.add_child(&info_bar_text);
-
-        let status_label = gtk::Label::new(None);
-
         let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
         vbox.append(&top_bar);
-        vbox.append(&content);
-        vbox.append(&info_bar);
-        vbox.append(&status_label);
+        vbox.append(&notebook);
 
         let app_window = gtk::ApplicationWindow::builder()
             .application(app)
@@ -97,13 +178,12 @@ impl Window {
 
         app_window.present();
 
-        let location = String::from("");
         let mut http_client_builder = reqwest::blocking::Client::builder()
-            .cookie_store(true)
+            .cookie_provider(cookie_jar.clone())
             .user_agent("GTK Webby")
             .default_headers(crate::headers::request_headers());
 
-        for cert in &globals.root_certs {
+        for cert in root_certs {
             http_client_builder = http_client_builder.add_root_certificate(cert.clone());
         }
 
@@ -111,45 +191,40 @@ impl Window {
             .build()
             .expect("failed to build http client");
 
-        let builder = gtk::Builder::new();
-        let user_styles = None;
-        let state = State {
-            globals,
-            location,
-            http_client,
-            builder,
-            user_styles,
-            history: crate::history::History::new(),
-            ui_definition: None,
-        };
         let window = Arc::new(Self {
             app_window,
+            notebook,
             back_button,
             forward_button,
             refresh_button,
             bookmark_button,
+            bookmark_popover,
+            bookmark_title_entry,
             address_entry,
-            content,
-            info_bar,
-            info_bar_text,
-            status_label,
-            state: Mutex::new(state),
+            address_popover,
+            address_suggestions,
+            bookmarks,
+            bookmarks_menu,
+            cookie_jar,
+            visits,
+            http_client,
+            tabs: Mutex::new(Vec::new()),
         });
 
-        crate::script::lua::init(window.clone());
-
         window
             .back_button
             .connect_clicked(clone!(@weak window => move |_| {
-                let location = window.state.lock().unwrap().history.back();
-                window.go(location, false);
+                if let Some(tab) = window.current_tab() {
+                    tab.back();
+                }
             }));
 
         window
             .forward_button
             .connect_clicked(clone!(@weak window => move |_| {
-                let location = window.state.lock().unwrap().history.forward();
-                window.go(location, false);
+                if let Some(tab) = window.current_tab() {
+                    tab.forward();
+                }
             }));
 
         window
@@ -158,20 +233,68 @@ impl Window {
                 window.reload();
             }));
 
+        // The switch-page signal is emitted before the current page changes, so the tab is found
+        // from the page that's being switched to.
         window
-            .bookmark_button
-            .connect_clicked(clone!(@weak window => move |_| {
-                eprintln!("TODO: add bookmark");
+            .notebook
+            .connect_switch_page(clone!(@weak window => move |_, page, _| {
+                if let Some(tab) = window.tab_for_page(page) {
+                    window.show_tab(&tab);
+                }
+            }));
+
+        window
+            .bookmark_popover
+            .connect_show(clone!(@weak window => move |_| {
+                window.bookmark_current_page();
             }));
 
+        window
+            .bookmark_title_entry
+            .connect_activate(clone!(@weak window => move |_| {
+                window.rename_current_bookmark();
+            }));
+
+        bookmark_done_button.connect_clicked(clone!(@weak window => move |_| {
+            window.rename_current_bookmark();
+        }));
+
+        bookmark_remove_button.connect_clicked(clone!(@weak window => move |_| {
+            let location = match window.current_tab() {
+                Some(tab) => tab.state.lock().unwrap().location.clone(),
+                None => return,
+            };
+            let removed = window.bookmarks.lock().unwrap().remove(&location);
+            if removed {
+                window.save_bookmarks();
+            }
+            window.bookmark_popover.popdown();
+        }));
+
         window
             .address_entry
             .connect_activate(clone!(@weak window => move |_| {
-                let location = window.address_entry.text().to_string();
+                let location = match window.selected_address_suggestion() {
+                    Some(location) => location,
+                    None => window.address_entry.text().to_string(),
+                };
                 window.go(location, true);
             }));
 
+        window
+            .app_window
+            .connect_close_request(clone!(@weak window => @default-return gtk::Inhibit(false), move |_| {
+                // Each page's script environment and signal handlers hold references to its
+                // tab, so they need to be released explicitly.
+                let tabs = window.tabs.lock().unwrap().clone();
+                for tab in tabs {
+                    tab.unload_page();
+                }
+                gtk::Inhibit(false)
+            }));
+
         window.define_actions();
+        window.open_tab(None, true);
         window
     }
 
@@ -185,199 +308,307 @@ impl Window {
         let open_source_editor = gio::SimpleAction::new("open-source-editor", None);
         open_source_editor.connect_activate(
             clone!(@weak self as window => move |_action, _param| {
+                // The editor renders into the tab that was shown when it was opened.
+                let tab = match window.current_tab() {
+                    Some(tab) => tab,
+                    None => return,
+                };
                 let app_window = window.app_window.clone();
-                let starting_text = window.state.lock().unwrap().ui_definition.as_ref().map(|def| def.source.clone());
+                let starting_text = tab.state.lock().unwrap().ui_definition.as_ref().map(|def| def.source.clone());
                 let editor = crate::editor::Editor::new(&app_window, starting_text, move |text| {
-                    if let Err(err) = window.clone().render_gtk(text) {
-                        println!("Failed to render: {}", err);
-                    }
+                    tab.clone().render_gtk(text, None, false)
                 });
                 editor.show();
             }),
         );
         self.app_window.add_action(&open_source_editor);
-    }
 
-    pub fn go(self: Arc<Self>, location: String, modify_history: bool) {
-        self.address_entry.set_text(&location);
-        self.info_bar.set_revealed(false);
-
-        // TODO: support file://
-
-        //println!("Navigating to: {}", &location);
-        self.state.lock().unwrap().ui_definition = None;
-        self.status_label
-            .set_label(&format!("Loading {}...", &location));
-        let request = self.state.lock().unwrap().http_client.get(&location);
-        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
-        std::thread::spawn(move || {
-            let response_result = request.send();
-            if let Err(err) = sender.send(response_result) {
-                println!("Failed to send response on channel: {}", err);
-            }
-        });
+        let open_cookie_manager = gio::SimpleAction::new("open-cookie-manager", None);
+        open_cookie_manager.connect_activate(clone!(@weak self as window => move |_action, _param| {
+            let cookie_manager =
+                crate::cookie_manager::CookieManager::new(&window.app_window, window.cookie_jar.clone());
+            cookie_manager.show();
+        }));
+        self.app_window.add_action(&open_cookie_manager);
 
-        receiver.attach(None, clone!(@weak self as window => @default-return Continue(false), move |response_result| {
-            let r#do = || -> crate::Result<()> {
-                let response = response_result?;
-                //println!("headers:");
-                //for header in response.headers().iter() {
-                //    println!("  {} = {:?}", header.0, header.1);
-                //}
+        let go = gio::SimpleAction::new("go", Some(glib::VariantTy::STRING));
+        go.connect_activate(clone!(@weak self as window => move |_action, param| {
+            if let Some(location) = param.and_then(|param| param.get::<String>()) {
+                window.go(location, true);
+            }
+        }));
+        self.app_window.add_action(&go);
 
-                window.content.set_child(gtk::Widget::NONE);
-                window.state.lock().unwrap().location = response.url().to_string();
+        let new_tab = gio::SimpleAction::new("new-tab", None);
+        new_tab.connect_activate(clone!(@weak self as window => move |_action, _param| {
+            window.open_tab(None, true);
+        }));
+        self.app_window.add_action(&new_tab);
 
-                let mime_type: mime::Mime = match response.headers().get(reqwest::header::CONTENT_TYPE) {
-                    Some(content_type) => content_type.to_str()?.parse()?,
-                    None => return Err(crate::error::Error::NoContentTypeError),
-                };
+        let close_tab = gio::SimpleAction::new("close-tab", None);
+        close_tab.connect_activate(clone!(@weak self as window => move |_action, _param| {
+            if let Some(tab) = window.current_tab() {
+                window.close_tab(&tab);
+            }
+        }));
+        self.app_window.add_action(&close_tab);
 
-                if let Some(charset) = mime_type.get_param("charset") {
-                    if charset.as_str() != "utf-8" {
-                        return Err(crate::error::Error::UnsupportedCharsetError(charset.as_str().into()));
-                    }
-                }
+        let import_bookmarks = gio::SimpleAction::new("import-bookmarks", None);
+        import_bookmarks.connect_activate(clone!(@weak self as window => move |_action, _param| {
+            window.choose_bookmarks_file(gtk::FileChooserAction::Open);
+        }));
+        self.app_window.add_action(&import_bookmarks);
 
-                match mime_type.type_() {
-                    mime::TEXT if mime_type.subtype() == "gtk" => window.clone().render_gtk(Self::read_all(response)?),
-                    mime::TEXT => window.clone().render_text(Self::read_all(response)?),
-                    mime::APPLICATION if mime_type.subtype() == "gtk" => window.clone().render_gtk(Self::read_all(response)?),
-                    _ => Err(crate::error::Error::UnsupportedContentTypeError(mime_type.essence_str().to_string())),
-                }
-            };
+        let export_bookmarks = gio::SimpleAction::new("export-bookmarks", None);
+        export_bookmarks.connect_activate(clone!(@weak self as window => move |_action, _param| {
+            window.choose_bookmarks_file(gtk::FileChooserAction::Save);
+        }));
+        self.app_window.add_action(&export_bookmarks);
+    }
 
-            if let Err(err) = r#do() {
-                let err_text = err.to_string().replace(": ", ":\n");
-                window.info_bar_text.set_text(&err_text);
-                window.info_bar.set_message_type(gtk::MessageType::Error);
-                window.info_bar.set_revealed(true);
-                println!("Navigation error: {}", err);
-            } else {
-                if modify_history {
-                    self.state.lock().unwrap().history.push(location.clone());
-                }
+    /// Bookmarks the current page, if it isn't already, and fills in its title for renaming.
+    fn bookmark_current_page(&self) {
+        let (location, title) = match self.current_tab() {
+            Some(tab) => {
+                let state = tab.state.lock().unwrap();
+                (state.location.clone(), state.title.clone())
             }
+            None => (String::new(), None),
+        };
+        if location.is_empty() {
+            self.bookmark_popover.popdown();
+            return;
+        }
 
-            self.back_button.set_sensitive(self.state.lock().unwrap().history.can_go_back());
-            self.forward_button.set_sensitive(self.state.lock().unwrap().history.can_go_forward());
-            self.refresh_button.set_sensitive(true);
+        let (added, title) = {
+            let mut bookmarks = self.bookmarks.lock().unwrap();
+            let added = bookmarks.add(title.unwrap_or(location.clone()), location.clone());
+            (added, bookmarks.get(&location).unwrap().title.clone())
+        };
+        if added {
+            self.save_bookmarks();
+        }
 
-            window.status_label.set_text("");
-            Continue(false)
-        }));
+        self.bookmark_title_entry.set_text(&title);
     }
 
-    fn read_all<R: Read>(mut r: R) -> std::io::Result<String> {
-        let mut s = String::new();
-        r.read_to_string(&mut s)?;
-        Ok(s)
+    fn rename_current_bookmark(&self) {
+        let location = match self.current_tab() {
+            Some(tab) => tab.state.lock().unwrap().location.clone(),
+            None => return,
+        };
+        let title = self.bookmark_title_entry.text().to_string();
+        if !title.is_empty() {
+            let renamed = self.bookmarks.lock().unwrap().rename(&location, title);
+            if renamed {
+                self.save_bookmarks();
+            }
+        }
+        self.bookmark_popover.popdown();
     }
 
-    fn render_text(self: Arc<Self>, s: String) -> crate::Result<()> {
-        self.content.set_child(Some(&gtk::TextView::with_buffer(
-            &gtk::TextBuffer::builder().text(&s).build(),
-        )));
-        Ok(())
+    /// Saves the bookmarks and updates the menu listing them.
+    pub fn save_bookmarks(&self) {
+        let bookmarks = self.bookmarks.lock().unwrap();
+        if let Err(err) = bookmarks.save() {
+            match self.current_tab() {
+                Some(tab) => tab.show_error(&err),
+                None => println!("Failed to save bookmarks: {}", err),
+            }
+        }
+        update_bookmarks_menu(&self.bookmarks_menu, &bookmarks);
     }
 
-    fn render_gtk(self: Arc<Self>, s: String) -> crate::Result<()> {
-        let ui_definition = crate::ui::Definition::new(s)?;
+    /// Asks for a file to import bookmarks from, or export them to, depending on `action`.
+    fn choose_bookmarks_file(self: Arc<Self>, action: gtk::FileChooserAction) {
+        let (title, accept_label) = match action {
+            gtk::FileChooserAction::Save => ("Export Bookmarks", "Export"),
+            _ => ("Import Bookmarks", "Import"),
+        };
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Bookmarks (JSON or XBEL)"));
+        filter.add_pattern("*.json");
+        filter.add_pattern("*.xbel");
+        let dialog = gtk::FileDialog::builder()
+            .title(title)
+            .accept_label(accept_label)
+            .default_filter(&filter)
+            .modal(true)
+            .build();
 
-        // Remove existing user-requested CSS styling, if there is any.
-        if let Some(user_styles) = self.state.lock().unwrap().user_styles.as_ref() {
-            gtk::style_context_remove_provider_for_display(&self.display(), user_styles);
-        }
-        self.state.lock().unwrap().user_styles = None;
-
-        // If the new page has styles, apply them.
-        if !ui_definition.styles.is_empty() {
-            let user_styles = gtk::CssProvider::new();
-            user_styles.load_from_data(ui_definition.styles.as_str());
-            gtk::style_context_add_provider_for_display(
-                &self.display(),
-                &user_styles,
-                gtk::STYLE_PROVIDER_PRIORITY_USER,
-            );
-            self.state.lock().unwrap().user_styles = Some(user_styles);
+        let on_chosen = clone!(@weak self as window => move |result: Result<gio::File, glib::Error>| {
+            // Cancelling the dialog is reported as an error too.
+            let path = match result.map(|file| file.path()) {
+                Ok(Some(path)) => path,
+                _ => return,
+            };
+            let result = if action == gtk::FileChooserAction::Save {
+                window.bookmarks.lock().unwrap().export(&path).map(|()| {
+                    format!("Exported bookmarks to {}", path.display())
+                })
+            } else {
+                let result = window.bookmarks.lock().unwrap().import(&path);
+                window.save_bookmarks();
+                result.map(|count| format!("Imported {} bookmarks", count))
+            };
+            if let Some(tab) = window.current_tab() {
+                match result {
+                    Ok(message) => tab.set_status(&message),
+                    Err(err) => tab.show_error(&err),
+                }
+            }
+        });
+        if action == gtk::FileChooserAction::Save {
+            dialog.set_initial_name(Some("bookmarks.json"));
+            dialog.save(Some(&self.app_window), gio::Cancellable::NONE, on_chosen);
+        } else {
+            dialog.open(Some(&self.app_window), gio::Cancellable::NONE, on_chosen);
         }
+    }
 
-        // Set the window title to that requested by the user, or the location if there was
-        // none.
-        self.app_window.set_title(Some(
-            &ui_definition
-                .title
-                .as_ref()
-                .map(|s| s.clone())
-                .unwrap_or(self.state.lock().unwrap().location.clone()),
-        ));
-
-        // Construct the GTK builder from the UI definition.
-        let builder = gtk::Builder::new();
-        builder.add_from_string(&ui_definition.buildable)?;
-
-        // Find the "body" widget, and set it as the window's content.
-        match builder.object::<gtk::Widget>("body") {
-            Some(body) /* once told me */ => self.content.set_child(Some(&body)),
-            None => println!("No object found named 'body'"),
+    /// Returns the location of the address suggestion chosen with the arrow keys, if there is one.
+    fn selected_address_suggestion(&self) -> Option<String> {
+        if !self.address_popover.is_visible() {
+            return None;
         }
+        self.address_suggestions
+            .selected_row()?
+            .action_target_value()?
+            .get::<String>()
+    }
 
-        // Set up callbacks for any href attributes.
-        for (object_id, target) in &ui_definition.hrefs {
-            let window = self.clone();
-            let target = target.clone();
-            match builder.object::<gtk::Widget>(object_id) {
-                Some(widget) => {
-                    widget.connect_local("clicked", false, move |_| {
-                        window.clone().href(&target);
-                        None
-                    });
-                }
-                None => println!(
-                    "href: no object with id, or object is of the wrong type: {}",
-                    object_id
-                ),
+    /// Returns the tab being shown, if there is one.
+    pub fn current_tab(&self) -> Option<Arc<Tab>> {
+        let page = self.notebook.nth_page(Some(self.notebook.current_page()?))?;
+        self.tab_for_page(&page)
+    }
+
+    fn tab_for_page(&self, page: &gtk::Widget) -> Option<Arc<Tab>> {
+        self.tabs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|tab| tab.widget.upcast_ref::<gtk::Widget>() == page)
+            .cloned()
+    }
+
+    /// Opens a new tab after the others, navigating to `location` if there is one. If `switch` is
+    /// true, the new tab is shown.
+    pub fn open_tab(self: &Arc<Self>, location: Option<String>, switch: bool) -> Arc<Tab> {
+        let tab = Tab::new(
+            Arc::downgrade(self),
+            self.http_client.clone(),
+            self.visits.clone(),
+        );
+        self.tabs.lock().unwrap().push(tab.clone());
+        let page = self.notebook.append_page(&tab.widget, Some(&tab.header));
+        self.notebook.set_tab_reorderable(&tab.widget, true);
+        if switch {
+            self.notebook.set_current_page(Some(page));
+            if location.is_none() {
+                self.address_entry.grab_focus();
             }
         }
+        if let Some(location) = location {
+            tab.clone().go(location, true);
+        }
+        tab
+    }
 
-        self.state.lock().unwrap().builder = builder;
-
-        // Run any defined scripts.
-        for script in &ui_definition.scripts {
-            script.execute(&self);
+    /// Closes a tab, releasing its page. Closing the last tab closes the window.
+    pub fn close_tab(&self, tab: &Tab) {
+        tab.unload_page();
+        if let Some(page) = self.notebook.page_num(&tab.widget) {
+            self.notebook.remove_page(Some(page));
         }
 
-        self.state.lock().unwrap().ui_definition = Some(ui_definition);
+        let is_empty = {
+            let mut tabs = self.tabs.lock().unwrap();
+            tabs.retain(|t| !std::ptr::eq(t.as_ref(), tab));
+            tabs.is_empty()
+        };
+        if is_empty {
+            self.app_window.close();
+        }
+    }
 
-        // Clean up any old Lua registry values, such as now-unreferenced callbacks.
-        self.state.lock().unwrap().globals.lua.expire_registry_values();
+    /// Updates the address bar, buttons and title if `tab` is the one being shown.
+    pub fn tab_changed(&self, tab: &Tab) {
+        let page = self.notebook.page_num(&tab.widget);
+        if page.is_some() && page == self.notebook.current_page() {
+            self.show_tab(tab);
+        }
+    }
 
-        Ok(())
+    fn show_tab(&self, tab: &Tab) {
+        let state = tab.state.lock().unwrap();
+        self.address_entry.set_text(&state.address);
+        // Setting the address isn't typing it, so it shouldn't be suggested for.
+        self.address_popover.popdown();
+        self.back_button.set_sensitive(state.history.can_go_back());
+        self.forward_button.set_sensitive(state.history.can_go_forward());
+        self.refresh_button.set_sensitive(!state.location.is_empty());
+        self.bookmark_button.set_sensitive(!state.location.is_empty());
+        self.app_window.set_title(Some(state.title.as_deref().unwrap_or("Webby")));
     }
 
-    fn href(self: Arc<Self>, target: &String) {
-        let location = crate::util::absolutize_url(&self.state.lock().unwrap().location, target);
-        self.address_entry.set_text(&location);
-        self.go(location, true);
+    /// Navigates the current tab to `location`.
+    pub fn go(self: Arc<Self>, location: String, modify_history: bool) {
+        self.address_popover.popdown();
+        if let Some(tab) = self.current_tab() {
+            tab.go(location, modify_history);
+        }
     }
 
+    /// Reloads the current tab.
     pub fn reload(self: Arc<Self>) {
-        let location = self.state.lock().unwrap().location.clone();
-        if !location.is_empty() {
-            self.go(location, false);
+        if let Some(tab) = self.current_tab() {
+            tab.reload();
         }
     }
 
-    pub fn alert(self: Arc<Self>, text: &str) {
-        let dialog = gtk::Dialog::builder()
-            .title("Alert")
-            .child(&gtk::Label::new(Some(text)))
-            .transient_for(&self.app_window)
+    /// Reloads every tab, such as when a watched file changes.
+    pub fn reload_all(&self) {
+        let tabs = self.tabs.lock().unwrap().clone();
+        for tab in tabs {
+            tab.reload();
+        }
+    }
+}
+
+/// Builds a row suggesting a previously visited page, which goes to it when activated.
+fn address_suggestion_row(url: &str, title: Option<&str>) -> gtk::ListBoxRow {
+    let url_label = gtk::Label::builder()
+        .label(url)
+        .xalign(0.0)
+        .ellipsize(pango::EllipsizeMode::Middle)
+        .max_width_chars(60)
+        .build();
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    vbox.append(&url_label);
+    if let Some(title) = title {
+        let title_label = gtk::Label::builder()
+            .label(title)
+            .xalign(0.0)
+            .ellipsize(pango::EllipsizeMode::End)
+            .max_width_chars(60)
             .build();
-        dialog.present();
+        title_label.add_css_class("dim-label");
+        vbox.append(&title_label);
     }
 
-    fn display(&self) -> gdk::Display {
-        RootExt::display(&self.app_window)
+    let row = gtk::ListBoxRow::builder().child(&vbox).build();
+    row.set_action_name(Some("win.go"));
+    row.set_action_target_value(Some(&url.to_variant()));
+    row
+}
+
+/// Replaces the items in the bookmarks menu with the current bookmarks.
+pub fn update_bookmarks_menu(menu: &gio::Menu, bookmarks: &Bookmarks) {
+    menu.remove_all();
+    for bookmark in bookmarks.list() {
+        let item = gio::MenuItem::new(Some(&bookmark.title), None);
+        item.set_action_and_target_value(Some("win.go"), Some(&bookmark.url.to_variant()));
+        menu.append_item(&item);
     }
 }